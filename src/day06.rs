@@ -1,36 +1,155 @@
-type TimeDistance = (f64, f64);
-
-// const TEST: [TimeDistance; 3] = [(7.0, 9.0), (15.0, 40.0), (30.0, 200.0)];
-const DATA: [TimeDistance; 4] = [
-    (48.0, 261.0),
-    (93.0, 1192.0),
-    (84.0, 1019.0),
-    (66.0, 1063.0),
-];
-
-fn possible_wins(td: TimeDistance) -> u64 {
-    let discriminant = td.0.powi(2) - 4.0 * td.1;
-    let r1 = (td.0 - discriminant.sqrt()) / 2.0;
-    let r2 = (td.0 + discriminant.sqrt()) / 2.0;
-    // need next integer larger/smaller even if already integer
-    let i1 = (r1 + 1.0).floor() as u64;
-    let i2 = (r2 - 1.0).ceil() as u64;
-    i2 - i1 + 1
+use num::{BigUint, One, ToPrimitive, Zero};
+
+use crate::util::load;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Race {
+    pub time: BigUint,
+    pub distance: BigUint,
+}
+
+impl Race {
+    fn wins(&self, hold: &BigUint) -> bool {
+        hold * (&self.time - hold) > self.distance
+    }
+
+    // number of hold times (in ms) that beat the record distance
+    pub fn possible_wins(&self) -> BigUint {
+        // h * (T - h) > D  <=>  h^2 - T*h + D < 0
+        let t2 = &self.time * &self.time;
+        let d4 = &self.distance * 4u32;
+        if t2 <= d4 {
+            return BigUint::zero();
+        }
+        // smallest root, rounded down, is at most one off from the first win
+        let mut first = (&self.time - (t2 - d4).sqrt()) >> 1;
+        while first > BigUint::zero() && self.wins(&(&first - 1u32)) {
+            first -= 1u32;
+        }
+        while &first * 2u32 <= self.time && !self.wins(&first) {
+            first += 1u32;
+        }
+        if &first * 2u32 > self.time {
+            return BigUint::zero();
+        }
+        // wins are symmetric around T/2
+        &self.time - &first * 2u32 + BigUint::one()
+    }
+}
+
+fn numbers<'a>(lines: &'a [String], idx: usize, label: &str) -> Vec<&'a str> {
+    assert!(lines.len() >= 2, "Expected 'Time:' and 'Distance:' lines");
+    let mut tokens = lines[idx].split_whitespace();
+    assert_eq!(
+        tokens.next(),
+        Some(label),
+        "Expected line {} to start with '{}'",
+        idx + 1,
+        label
+    );
+    tokens.collect()
+}
+
+fn times(lines: &[String]) -> Vec<&str> {
+    numbers(lines, 0, "Time:")
+}
+
+fn distances(lines: &[String]) -> Vec<&str> {
+    numbers(lines, 1, "Distance:")
+}
+
+// every column is a separate race
+pub fn races(lines: &[String]) -> Vec<Race> {
+    times(lines)
+        .into_iter()
+        .zip(distances(lines))
+        .map(|(t, d)| Race {
+            time: t.parse().unwrap(),
+            distance: d.parse().unwrap(),
+        })
+        .collect()
+}
+
+// bad kerning: all columns form a single race
+pub fn kerned_race(lines: &[String]) -> Race {
+    Race {
+        time: times(lines).concat().parse().unwrap(),
+        distance: distances(lines).concat().parse().unwrap(),
+    }
+}
+
+fn input() -> Vec<String> {
+    load::<String>("data/day06.txt")
 }
 
 pub fn part1() -> u64 {
-    DATA.into_iter().map(|td| possible_wins(td)).product()
+    races(&input())
+        .iter()
+        .map(|r| r.possible_wins())
+        .product::<BigUint>()
+        .to_u64()
+        .unwrap()
 }
 
 pub fn part2() -> u64 {
-    // Need 64-bit numbers for precision
-    possible_wins((48938466.0, 261119210191063.0))
+    kerned_race(&input()).possible_wins().to_u64().unwrap()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn sample() -> Vec<String> {
+        vec![
+            "Time:      7  15   30".to_string(),
+            "Distance:  9  40  200".to_string(),
+        ]
+    }
+
+    fn race(time: u64, distance: u64) -> Race {
+        Race {
+            time: time.into(),
+            distance: distance.into(),
+        }
+    }
+
+    #[test]
+    fn test_sample_races() {
+        let wins: Vec<BigUint> = races(&sample()).iter().map(|r| r.possible_wins()).collect();
+        assert_eq!(wins, vec![4u32.into(), 8u32.into(), 9u32.into()]);
+    }
+
+    #[test]
+    fn test_kerned_race() {
+        assert_eq!(kerned_race(&sample()).possible_wins(), 71503u32.into());
+    }
+
+    #[test]
+    fn test_no_possible_win() {
+        // T^2 == 4D: touching the record is not beating it
+        assert_eq!(race(4, 4).possible_wins(), BigUint::zero());
+        // T^2 > 4D but roots 2 and 3 are integers, so nothing strictly in between
+        assert_eq!(race(5, 6).possible_wins(), BigUint::zero());
+    }
+
+    #[test]
+    fn test_huge_race() {
+        let huge = Race {
+            time: "1000000000000000000000000000000".parse().unwrap(),
+            distance: 0u32.into(),
+        };
+        assert_eq!(
+            huge.possible_wins(),
+            "999999999999999999999999999999".parse().unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Distance:")]
+    fn test_missing_label() {
+        races(&["Time: 7".to_string(), "9".to_string()]);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 1312850);
@@ -1,4 +1,10 @@
-use std::{cmp::Ordering, str::FromStr};
+use std::{
+    cmp::Ordering,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use itertools::Itertools;
 
 use crate::util::load;
 
@@ -14,183 +20,336 @@ pub enum HandCategory {
     HighCard = 1,
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TieBreak {
+    // compare cards left to right, as dealt
+    Dealt,
+    // compare cards right to left
+    Reversed,
+    // compare cards from strongest to weakest, as in poker
+    Strongest,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rules {
+    // cards from weakest to strongest
+    pub ranking: Vec<char>,
+    // cards that act as whatever makes the hand strongest
+    pub wildcards: Vec<char>,
+    pub hand_size: usize,
+    pub tie_break: TieBreak,
+}
+
+impl Rules {
+    pub fn new(ranking: &str, wildcards: &str, hand_size: usize, tie_break: TieBreak) -> Self {
+        Rules {
+            ranking: ranking.chars().collect(),
+            wildcards: wildcards.chars().collect(),
+            hand_size,
+            tie_break,
+        }
+    }
+
+    pub fn standard() -> Self {
+        Rules::new("23456789TJQKA", "", 5, TieBreak::Dealt)
+    }
+
+    pub fn jokers() -> Self {
+        Rules::new("J23456789TQKA", "J", 5, TieBreak::Dealt)
+    }
+
+    fn card2value(&self, card: char) -> u32 {
+        match self.ranking.iter().position(|c| *c == card) {
+            Some(p) => p as u32,
+            None => panic!("Card '{}' not in ranking", card),
+        }
+    }
+
+    fn is_wild(&self, card: char) -> bool {
+        self.wildcards.contains(&card)
+    }
+}
+
+// Sizes of the groups of equal cards, largest first. Comparing these
+// lexicographically orders hands of any size, e.g. [3, 2] > [3, 1, 1].
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Category(Vec<usize>);
+
+impl Category {
+    pub fn groups(&self) -> &[usize] {
+        &self.0
+    }
+
+    // named category, only for five-card hands
+    pub fn kind(&self) -> Option<HandCategory> {
+        match self.0.as_slice() {
+            [5] => Some(HandCategory::FiveOfAKind),
+            [4, 1] => Some(HandCategory::FourOfAKind),
+            [3, 2] => Some(HandCategory::FullHouse),
+            [3, 1, 1] => Some(HandCategory::ThreeOfAKind),
+            [2, 2, 1] => Some(HandCategory::TwoPairs),
+            [2, 1, 1, 1] => Some(HandCategory::OnePair),
+            [1, 1, 1, 1, 1] => Some(HandCategory::HighCard),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self.kind() {
+            Some(kind) => format!("{:?}", kind),
+            None => self.0.iter().join("+"),
+        };
+        f.pad(&name)
+    }
+}
+
 #[derive(Debug, Eq)]
 pub struct Hand {
+    // cards as dealt
+    text: String,
+    // card values in tie-break order
     cards: Vec<u32>,
     bid: u32,
-    category: HandCategory,
+    category: Category,
+    // card the wildcards stand in for, if any
+    wild_as: Option<char>,
 }
 
 impl FromStr for Hand {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        fn card2value(card: &char) -> u32 {
-            match card {
-                'T' => 10,
-                'J' => 11,
-                'Q' => 12,
-                'K' => 13,
-                'A' => 14,
-                _ => card.to_digit(10).unwrap(),
-            }
-        }
-        let tokens: Vec<&str> = s.split_whitespace().collect();
-        let cards = tokens[0]
-            .chars()
-            .map(|c| card2value(&c))
-            .collect::<Vec<u32>>();
-        let bid = tokens[1].parse::<u32>().unwrap();
-        Ok(Hand {
-            cards,
-            bid,
-            category: HandCategory::HighCard,
-        })
+        Ok(Hand::new(s, &Rules::standard()))
     }
 }
 
 impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.category
+            .cmp(&other.category)
+            .then_with(|| self.cards.cmp(&other.cards))
     }
 }
 
 impl PartialEq for Hand {
     fn eq(&self, other: &Self) -> bool {
-        Some(Ordering::Equal) == self.partial_cmp(other)
+        self.cmp(other) == Ordering::Equal
     }
 }
 
 impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self.category.partial_cmp(&other.category) {
-            Some(Ordering::Equal) => {
-                match self
-                    .cards
-                    .iter()
-                    .zip(other.cards.iter())
-                    .skip_while(|(a, b)| a == b)
-                    .next()
-                {
-                    None => Some(Ordering::Equal),
-                    Some((a, b)) => a.partial_cmp(&b),
-                }
-            }
-            o => o,
-        }
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl Hand {
-    /* Longest  Jokers  Longest+Jokers  Other  Pairs  Result
-         5        0            5          0      2    Five-of-a-kind
-         4        0            4          1      2    Four-of-a-kind
-         4        1            5          0      2    Five-of-a-kind
-         3        0            3          2      1    Three-of-a-kind
-         3        0            3          2      2    Full house
-         3        1            4          1      1    Four-of-a-kind
-         3        2            5          0      1    Five-of-a-kind
-         2        0            2          3      1    One pair
-         2        0            2          3      2    Two pair
-         2        1            3          2      1    Three-of-a-kind
-         2        1            3          2      2    Full house
-         2        2            4          1      1    Four-of-a-kind
-         2        3            5          0      1    Five-of-a-kind
-         1        0            1          4      0    High card
-         1        1            2          3      0    One pair
-         1        2            3          2      0    Three-of-a-kind
-         1        3            4          1      0    Four-of-a-kind
-         1        4            5          0      0    Five-of-a-kind
-    */
-    pub fn categorise(mut self) -> Self {
-        let mut sorted = self.cards.clone();
-        sorted.sort();
-        let mut pair_count = 0;
-        let mut longest_equals = 0;
-        let mut num_equals = 1;
-        let mut prev = sorted[0];
-        let mut jokers = if prev == 1 { 1 } else { 0 };
-        sorted.into_iter().skip(1).for_each(|c| {
-            if c == 1 {
-                jokers += 1;
-            } else {
-                if c == prev {
-                    num_equals += 1;
-                } else {
-                    num_equals = 1;
-                }
-                if num_equals == 2 {
-                    pair_count += 1;
-                }
-                if num_equals > longest_equals {
-                    longest_equals = num_equals;
-                }
-            }
-            prev = c;
+    pub fn new(s: &str, rules: &Rules) -> Self {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        let dealt: Vec<char> = tokens[0].chars().collect();
+        if dealt.len() != rules.hand_size {
+            panic!(
+                "Hand '{}' has {} cards, expected {}",
+                tokens[0],
+                dealt.len(),
+                rules.hand_size
+            );
+        }
+        let mut cards: Vec<u32> = dealt.iter().map(|c| rules.card2value(*c)).collect();
+        match rules.tie_break {
+            TieBreak::Dealt => {}
+            TieBreak::Reversed => cards.reverse(),
+            TieBreak::Strongest => cards.sort_by(|a, b| b.cmp(a)),
+        }
+        let (category, wild_as) = Hand::categorise(&dealt, rules);
+        Hand {
+            text: tokens[0].to_string(),
+            cards,
+            bid: tokens[1].parse::<u32>().unwrap(),
+            category,
+            wild_as,
+        }
+    }
+
+    // wildcards always do best by joining the largest group
+    fn categorise(dealt: &[char], rules: &Rules) -> (Category, Option<char>) {
+        let wilds = dealt.iter().filter(|c| rules.is_wild(**c)).count();
+        let mut groups: Vec<(usize, char)> = dealt
+            .iter()
+            .filter(|c| !rules.is_wild(**c))
+            .counts()
+            .into_iter()
+            .map(|(c, n)| (n, *c))
+            .collect();
+        // largest first, strongest card first among equally large groups
+        groups.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| rules.card2value(b.1).cmp(&rules.card2value(a.1)))
         });
-        self.category = if longest_equals + jokers == 5 {
-            HandCategory::FiveOfAKind
-        } else if longest_equals + jokers >= 4 {
-            HandCategory::FourOfAKind
-        } else if longest_equals + jokers >= 3 {
-            if pair_count == 2 {
-                HandCategory::FullHouse
-            } else {
-                HandCategory::ThreeOfAKind
+        let wild_as = match groups.first_mut() {
+            _ if wilds == 0 => None,
+            Some((largest, card)) => {
+                *largest += wilds;
+                Some(*card)
             }
-        } else if longest_equals + jokers >= 2 {
-            if pair_count == 2 {
-                HandCategory::TwoPairs
-            } else {
-                HandCategory::OnePair
+            None => {
+                let strongest = *rules.ranking.last().unwrap();
+                groups.push((wilds, strongest));
+                Some(strongest)
             }
-        } else {
-            HandCategory::HighCard
         };
-        self
+        (Category(groups.into_iter().map(|g| g.0).collect()), wild_as)
     }
 }
 
-fn input() -> Vec<Hand> {
+#[derive(Debug)]
+pub struct RankedHand {
+    pub hand: String,
+    pub category: Category,
+    pub wild_as: Option<char>,
+    pub rank: u32,
+    pub bid: u32,
+    pub winnings: u32,
+}
+
+#[derive(Debug)]
+pub struct Ranking(pub Vec<RankedHand>);
+
+impl Ranking {
+    pub fn total(&self) -> u32 {
+        self.0.iter().map(|h| h.winnings).sum()
+    }
+}
+
+impl Display for Ranking {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .0
+            .iter()
+            .map(|h| h.hand.len())
+            .max()
+            .unwrap_or(0)
+            .max(4);
+        writeln!(
+            f,
+            "{:>5}  {:<width$}  {:<12}  {:<5}  {:>5}  {:>10}",
+            "Rank", "Hand", "Category", "Wild", "Bid", "Winnings"
+        )?;
+        for h in self.0.iter() {
+            let wild = match h.wild_as {
+                Some(c) => format!("-> {}", c),
+                None => "".to_string(),
+            };
+            writeln!(
+                f,
+                "{:>5}  {:<width$}  {:<12}  {:<5}  {:>5}  {:>10}",
+                h.rank, h.hand, h.category, wild, h.bid, h.winnings
+            )?;
+        }
+        writeln!(
+            f,
+            "{:>5}  {:<width$}  {:<12}  {:<5}  {:>5}  {:>10}",
+            "",
+            "",
+            "",
+            "",
+            "Total",
+            self.total()
+        )
+    }
+}
+
+fn input() -> Vec<String> {
     load::<String>("data/day07.txt")
-        .into_iter()
-        .map(|line| line.parse::<Hand>().unwrap())
-        .collect()
-}
-
-fn total_winnings(with_joker: bool) -> u32 {
-    let mut hands: Vec<Hand> = input()
-        .into_iter()
-        .map(|mut h| {
-            h.cards = h
-                .cards
-                .into_iter()
-                .map(|c| if c == 11 && with_joker { 1 } else { c })
-                .collect();
-            h
-        })
-        .map(|h| h.categorise())
+}
+
+// all hands from weakest to strongest, with how they got there
+pub fn explain<S: AsRef<str>>(lines: &[S], rules: &Rules) -> Ranking {
+    let mut hands: Vec<Hand> = lines
+        .iter()
+        .map(|line| Hand::new(line.as_ref(), rules))
         .collect();
     hands.sort();
-    hands
-        .into_iter()
-        .enumerate()
-        .map(|(i, h)| (i + 1) as u32 * h.bid)
-        .sum()
+    Ranking(
+        hands
+            .into_iter()
+            .enumerate()
+            .map(|(i, h)| RankedHand {
+                hand: h.text,
+                category: h.category,
+                wild_as: h.wild_as,
+                rank: (i + 1) as u32,
+                bid: h.bid,
+                winnings: (i + 1) as u32 * h.bid,
+            })
+            .collect(),
+    )
+}
+
+pub fn total_winnings<S: AsRef<str>>(lines: &[S], rules: &Rules) -> u32 {
+    explain(lines, rules).total()
 }
 
 pub fn part1() -> u32 {
-    total_winnings(false)
+    total_winnings(&input(), &Rules::standard())
 }
 
 pub fn part2() -> u32 {
-    total_winnings(true)
+    total_winnings(&input(), &Rules::jokers())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const SAMPLE: [&str; 5] = [
+        "32T3K 765",
+        "T55J5 684",
+        "KK677 28",
+        "KTJJT 220",
+        "QQQJA 483",
+    ];
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(total_winnings(&SAMPLE, &Rules::standard()), 6440);
+        assert_eq!(total_winnings(&SAMPLE, &Rules::jokers()), 5905);
+    }
+
+    #[test]
+    fn test_explain() {
+        let ranking = explain(&SAMPLE, &Rules::jokers());
+        let order: Vec<&str> = ranking.0.iter().map(|h| h.hand.as_str()).collect();
+        assert_eq!(order, vec!["32T3K", "KK677", "T55J5", "QQQJA", "KTJJT"]);
+        let last = &ranking.0[4];
+        assert_eq!(last.category.kind(), Some(HandCategory::FourOfAKind));
+        assert_eq!(last.wild_as, Some('T'));
+        assert_eq!(last.winnings, 5 * 220);
+        assert_eq!(ranking.0[1].wild_as, None);
+        let table = ranking.to_string();
+        assert!(table.contains("    5  KTJJT  FourOfAKind   -> T     220        1100"));
+        assert!(table.trim_end().ends_with("5905"));
+    }
+
+    #[test]
+    fn test_variants() {
+        // jokers and deuces wild: "2JJ22" is five of a kind
+        let rules = Rules::new("2J3456789TQKA", "2J", 5, TieBreak::Dealt);
+        let h = Hand::new("2JJ22 1", &rules);
+        assert_eq!(h.category.kind(), Some(HandCategory::FiveOfAKind));
+        // six-card hands: a triple beats three pairs
+        let rules = Rules::new("23456789TJQKA", "", 6, TieBreak::Dealt);
+        assert!(Hand::new("AAAA23 1", &rules) > Hand::new("AAA234 1", &rules));
+        assert!(Hand::new("AAA234 1", &rules) > Hand::new("KKQQJJ 1", &rules));
+        // reversed ranking and tie-break
+        let rules = Rules::new("AKQJT98765432", "", 5, TieBreak::Reversed);
+        assert!(Hand::new("2345K 1", &rules) > Hand::new("2345A 1", &rules));
+        assert!(Hand::new("A2345 1", &rules) > Hand::new("2345A 1", &rules));
+        assert_eq!(total_winnings(&SAMPLE, &rules), 6641);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 246912307);
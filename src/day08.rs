@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 
+use itertools::Itertools;
 use num::Integer;
 use regex::Regex;
 
-use crate::util::load;
+use crate::util::{find_cycle, load};
 
 #[derive(Debug)]
 pub enum Direction {
@@ -32,9 +33,13 @@ impl Node {
 type Instructions = HashMap<Node, (Node, Node)>;
 
 fn input() -> (Vec<Direction>, Instructions) {
+    parse(&load::<String>("data/day08.txt"))
+}
+
+fn parse<S: AsRef<str>>(lines: &[S]) -> (Vec<Direction>, Instructions) {
     let re = Regex::new(r"([0-9A-Z]+) = \(([0-9A-Z]+), ([0-9A-Z]+)\)").unwrap();
-    let lines = load::<String>("data/day08.txt");
     let dirs = lines[0]
+        .as_ref()
         .chars()
         .map(|c| match c {
             'L' => Direction::Left,
@@ -42,20 +47,17 @@ fn input() -> (Vec<Direction>, Instructions) {
             _ => unreachable!("Invalid direction"),
         })
         .collect();
-    let instr = lines
-        .into_iter()
-        .skip(2)
-        .fold(HashMap::new(), |mut acc, l| {
-            let caps = re.captures(&l).unwrap();
-            acc.insert(
-                Node::new(caps.get(1).unwrap().as_str()),
-                (
-                    Node::new(caps.get(2).unwrap().as_str()),
-                    Node::new(caps.get(3).unwrap().as_str()),
-                ),
-            );
-            acc
-        });
+    let instr = lines.iter().skip(2).fold(HashMap::new(), |mut acc, l| {
+        let caps = re.captures(l.as_ref()).unwrap();
+        acc.insert(
+            Node::new(caps.get(1).unwrap().as_str()),
+            (
+                Node::new(caps.get(2).unwrap().as_str()),
+                Node::new(caps.get(3).unwrap().as_str()),
+            ),
+        );
+        acc
+    });
     (dirs, instr)
 }
 
@@ -76,6 +78,94 @@ fn go_to_end(dirs: &[Direction], instr: &Instructions, pos: &Node) -> usize {
     i
 }
 
+// The walk of a single ghost: every (node, instruction index) state is
+// visited once during the prefix, after which the states repeat with the
+// given period.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cycle {
+    pub prefix: usize,
+    pub period: usize,
+    // all steps (before prefix + period) at which the ghost is on an end node
+    pub ends: Vec<usize>,
+}
+
+impl Cycle {
+    pub fn is_end_at(&self, step: usize) -> bool {
+        if step < self.prefix {
+            self.ends.contains(&step)
+        } else {
+            let offset = self.prefix + (step - self.prefix) % self.period;
+            self.ends.contains(&offset)
+        }
+    }
+
+    fn cyclic_ends(&self) -> impl Iterator<Item = &usize> {
+        self.ends.iter().filter(|e| **e >= self.prefix)
+    }
+}
+
+pub fn analyse(dirs: &[Direction], instr: &Instructions, start: &Node) -> Cycle {
+    let walk = find_cycle((start, 0), |(p, idx)| {
+        let next = match dirs[*idx] {
+            Direction::Left => &instr.get(*p).unwrap().0,
+            Direction::Right => &instr.get(*p).unwrap().1,
+        };
+        (next, (idx + 1) % dirs.len())
+    });
+    Cycle {
+        prefix: walk.prefix,
+        period: walk.period,
+        ends: walk
+            .states
+            .iter()
+            .enumerate()
+            .filter(|(_, (p, _))| p.is_end)
+            .map(|(step, _)| step)
+            .collect(),
+    }
+}
+
+// solve t = r1 (mod m1) and t = r2 (mod m2), moduli need not be coprime
+fn crt((r1, m1): (i128, i128), (r2, m2): (i128, i128)) -> Option<(i128, i128)> {
+    let egcd = m1.extended_gcd(&m2);
+    let g = egcd.gcd;
+    if (r2 - r1) % g != 0 {
+        return None;
+    }
+    let lcm = m1 / g * m2;
+    let k = ((r2 - r1) / g * egcd.x).rem_euclid(m2 / g);
+    Some(((r1 + m1 * k).rem_euclid(lcm), lcm))
+}
+
+// first step at which all ghosts are on an end node at the same time
+pub fn first_common_end(cycles: &[Cycle]) -> Option<usize> {
+    let max_prefix = cycles.iter().map(|c| c.prefix).max()?;
+    // before all ghosts are cycling, just check every step
+    if let Some(t) = (0..max_prefix).find(|t| cycles.iter().all(|c| c.is_end_at(*t))) {
+        return Some(t);
+    }
+    // afterwards every ghost needs t = end (mod period) for one of its ends
+    cycles
+        .iter()
+        .map(|c| {
+            c.cyclic_ends()
+                .map(|e| ((e % c.period) as i128, c.period as i128))
+                .collect::<Vec<_>>()
+        })
+        .multi_cartesian_product()
+        .filter_map(|congruences| congruences.into_iter().try_fold((0, 1), crt))
+        .map(|(r, m)| {
+            let below = max_prefix as i128 - r;
+            let t = if below > 0 {
+                r + (below + m - 1) / m * m
+            } else {
+                r
+            };
+            t as usize
+        })
+        .min()
+}
+
 pub fn part1() -> usize {
     let (dirs, instr) = input();
     let pos = &Node::new("AAA");
@@ -84,18 +174,94 @@ pub fn part1() -> usize {
 
 pub fn part2() -> usize {
     let (dirs, instr) = input();
-    let starts: Vec<&Node> = instr.keys().filter(|n| n.is_start).collect();
-    let ends: Vec<usize> = starts
-        .into_iter()
-        .map(|p| go_to_end(&dirs, &instr, p))
+    let cycles: Vec<Cycle> = instr
+        .keys()
+        .filter(|n| n.is_start)
+        .map(|p| analyse(&dirs, &instr, p))
         .collect();
-    ends.into_iter().reduce(|acc, end| acc.lcm(&end)).unwrap()
+    first_common_end(&cycles).expect("Ghosts never all reach an end together")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn ghosts(lines: &[&str]) -> Vec<Cycle> {
+        let (dirs, instr) = parse(lines);
+        let mut starts: Vec<&Node> = instr.keys().filter(|n| n.is_start).collect();
+        starts.sort_by_key(|n| &n.name);
+        starts
+            .into_iter()
+            .map(|p| analyse(&dirs, &instr, p))
+            .collect()
+    }
+
+    #[test]
+    fn test_sample() {
+        let cycles = ghosts(&[
+            "LR",
+            "",
+            "11A = (11B, XXX)",
+            "11B = (XXX, 11Z)",
+            "11Z = (11B, XXX)",
+            "22A = (22B, XXX)",
+            "22B = (22C, 22C)",
+            "22C = (22Z, 22Z)",
+            "22Z = (22B, 22B)",
+            "XXX = (XXX, XXX)",
+        ]);
+        assert_eq!(
+            cycles[1],
+            Cycle {
+                prefix: 1,
+                period: 6,
+                ends: vec![3, 6]
+            }
+        );
+        assert_eq!(first_common_end(&cycles), Some(6));
+    }
+
+    #[test]
+    fn test_offset_cycles() {
+        // ends at odd steps only
+        let odd = Cycle {
+            prefix: 0,
+            period: 2,
+            ends: vec![1],
+        };
+        // ends at steps 2, 4, 6, ...
+        let even = Cycle {
+            prefix: 1,
+            period: 2,
+            ends: vec![2],
+        };
+        assert_eq!(first_common_end(&[odd, even]), None);
+        // step 4 during the prefix, then 9, 14, 19, ...
+        let a = Cycle {
+            prefix: 5,
+            period: 5,
+            ends: vec![4, 9],
+        };
+        // steps 1, 4, 7, ...
+        let b = Cycle {
+            prefix: 0,
+            period: 3,
+            ends: vec![1],
+        };
+        assert_eq!(first_common_end(&[a, b]), Some(4));
+        let c = Cycle {
+            prefix: 5,
+            period: 5,
+            ends: vec![9],
+        };
+        let b = Cycle {
+            prefix: 0,
+            period: 3,
+            ends: vec![1],
+        };
+        assert_eq!(first_common_end(&[c, b]), Some(19));
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 16043);
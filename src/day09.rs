@@ -1,61 +1,92 @@
+use num::{BigInt, ToPrimitive, Zero};
+
 use crate::util::load;
 
-fn input() -> Vec<Vec<i32>> {
+fn input() -> Vec<Vec<i64>> {
     let lines = load::<String>("data/day09.txt");
     lines
         .into_iter()
         .map(|l| {
             l.split_whitespace()
-                .map(|n| n.parse::<i32>().unwrap())
+                .map(|n| n.parse::<i64>().unwrap())
                 .collect()
         })
         .collect()
 }
 
-fn find_next(series: Vec<i32>) -> (i32, i32) {
-    fn recurse(s: Vec<i32>) -> (i32, i32) {
-        let all_the_same = s[1..]
-            .iter()
-            .fold((true, s[0]), |(same, prev), n| (same && (prev == *n), *n))
-            .0;
-        if all_the_same {
-            (s[0], *s.last().unwrap())
-        } else {
-            let (f, l) = recurse(
-                s[1..]
-                    .into_iter()
-                    .fold((vec![], s[0]), |(mut result, prev): (_, _), n| {
-                        result.push(prev - n);
-                        (result, *n)
-                    })
-                    .0,
-            );
-            (s[0] + f, s.last().unwrap() - l)
+// Newton's forward differences of a series, i.e. the first element of each
+// row of the difference table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polynomial {
+    diffs: Vec<BigInt>,
+}
+
+impl Polynomial {
+    pub fn fit(series: &[i64]) -> Self {
+        let mut row: Vec<BigInt> = series.iter().map(|n| BigInt::from(*n)).collect();
+        let mut diffs = vec![];
+        while !row.is_empty() && !row.iter().all(|n| n.is_zero()) {
+            diffs.push(row[0].clone());
+            row = row.windows(2).map(|w| &w[1] - &w[0]).collect();
         }
+        Polynomial { diffs }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.diffs.len().saturating_sub(1)
+    }
+
+    // value at any index, with 0 being the first element of the series
+    pub fn at(&self, index: i64) -> BigInt {
+        // f(n) = sum(C(n, k) * diff[k]), C(n, k) generalised to negative n
+        let n = BigInt::from(index);
+        let mut binomial = BigInt::from(1);
+        let mut value = BigInt::zero();
+        for (k, diff) in self.diffs.iter().enumerate() {
+            value += &binomial * diff;
+            binomial = binomial * (&n - k) / (k + 1);
+        }
+        value
     }
-    recurse(series)
 }
 
-pub fn part1() -> i32 {
+pub fn part1() -> i64 {
     let data = input();
     data.into_iter()
-        .map(|series| find_next(series))
-        .map(|fl| fl.1)
-        .sum()
+        .map(|series| Polynomial::fit(&series).at(series.len() as i64))
+        .sum::<BigInt>()
+        .to_i64()
+        .unwrap()
 }
 
-pub fn part2() -> i32 {
+pub fn part2() -> i64 {
     let data = input();
     data.into_iter()
-        .map(|series| find_next(series))
-        .map(|fl| fl.0)
-        .sum()
+        .map(|series| Polynomial::fit(&series).at(-1))
+        .sum::<BigInt>()
+        .to_i64()
+        .unwrap()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_extrapolate() {
+        let p = Polynomial::fit(&[10, 13, 16, 21, 30, 45]);
+        assert_eq!(p.degree(), 3);
+        assert_eq!(p.at(6), 68.into());
+        assert_eq!(p.at(-1), 5.into());
+        assert_eq!(Polynomial::fit(&[0, 3, 6, 9, 12, 15]).at(-1), (-3).into());
+        assert_eq!(Polynomial::fit(&[7, 7, 7]).degree(), 0);
+        // n^3 far ahead and far behind
+        let cubes = Polynomial::fit(&[0, 1, 8, 27, 64]);
+        let n = BigInt::from(1_000_000_000_000i64);
+        assert_eq!(cubes.at(1_000_000_000_000), &n * &n * &n);
+        assert_eq!(cubes.at(-1_000_000_000_000), -(&n * &n * &n));
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 1743490457);
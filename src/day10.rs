@@ -1,4 +1,9 @@
-use std::collections::HashSet;
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter},
+    fs::File,
+    io::{self, BufWriter, Write},
+};
 
 use crate::util::{load, Coord2D};
 
@@ -39,35 +44,22 @@ impl Tile {
         self.c2 = c2;
     }
 
-    fn _pipe(&self) -> char {
-        match self.c1 {
-            Direction::North => match self.c2 {
-                Direction::East => 'L',
-                Direction::South => '|',
-                Direction::West => 'J',
-                _ => '.',
-            },
-            Direction::East => match self.c2 {
-                Direction::North => 'L',
-                Direction::South => 'F',
-                Direction::West => '-',
-                _ => '.',
-            },
-            Direction::South => match self.c2 {
-                Direction::North => '|',
-                Direction::East => 'F',
-                Direction::West => '7',
-                _ => '.',
-            },
-            Direction::West => match self.c2 {
-                Direction::North => 'J',
-                Direction::East => '-',
-                Direction::South => '7',
-                _ => '.',
-            },
-            Direction::Void => '.',
+    fn box_char(&self) -> char {
+        use Direction::*;
+        match (self.c1, self.c2) {
+            (North, South) | (South, North) => '│',
+            (East, West) | (West, East) => '─',
+            (North, East) | (East, North) => '└',
+            (North, West) | (West, North) => '┘',
+            (South, West) | (West, South) => '┐',
+            (South, East) | (East, South) => '┌',
+            _ => '·',
         }
     }
+
+    fn is_pipe(&self) -> bool {
+        self.c1 != Direction::Void
+    }
 }
 
 type Coord = Coord2D<usize>;
@@ -85,13 +77,17 @@ impl Move {
 }
 
 fn input() -> (Coord, Grid) {
-    let lines = load::<String>("data/day10.txt");
+    parse(&load::<String>("data/day10.txt"))
+}
+
+fn parse<S: AsRef<str>>(lines: &[S]) -> (Coord, Grid) {
     let mut start = Coord2D::new(0, 0);
     let grid = lines
-        .into_iter()
+        .iter()
         .enumerate()
         .map(|(y, l)| {
-            l.chars()
+            l.as_ref()
+                .chars()
                 .enumerate()
                 .map(|(x, c)| match c {
                     '|' => Tile::new(Direction::North, Direction::South),
@@ -113,62 +109,100 @@ fn input() -> (Coord, Grid) {
     (start, grid)
 }
 
-fn find_starts(grid: &mut Grid, Coord2D { x, y }: Coord) -> (Move, Move) {
-    let mut dirs = vec![];
-    let mut starts = vec![];
-    if x > 0 {
-        if let Some(_) = grid[y][x - 1].connects(Direction::East) {
-            dirs.push(Direction::West);
-            starts.push(Move::new(Coord2D::new(x - 1, y), Direction::East));
-        }
-    }
-    if x < grid[0].len() - 1 {
-        if let Some(_) = grid[y][x + 1].connects(Direction::West) {
-            dirs.push(Direction::East);
-            starts.push(Move::new(Coord2D::new(x + 1, y), Direction::West));
+#[derive(Debug, PartialEq)]
+pub enum LoopError {
+    // number of neighbours that connect to the start tile
+    TooFewConnections(usize),
+    // none of the connecting neighbour pairs leads back to the start
+    NotClosed(usize),
+}
+
+impl Display for LoopError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoopError::TooFewConnections(n) => {
+                write!(f, "Start tile needs two connecting neighbours, found {}", n)
+            }
+            LoopError::NotClosed(n) => write!(
+                f,
+                "None of the {} connecting neighbours of the start tile form a closed loop",
+                n
+            ),
         }
     }
-    if y > 0 {
-        if let Some(_) = grid[y - 1][x].connects(Direction::South) {
-            dirs.push(Direction::North);
-            starts.push(Move::new(Coord2D::new(x, y - 1), Direction::South));
+}
+
+impl std::error::Error for LoopError {}
+
+// move one tile in direction d, if still on the grid
+fn step(grid: &Grid, Coord2D { x, y }: Coord, d: Direction) -> Option<Move> {
+    match d {
+        Direction::North if y > 0 => Some(Move::new(Coord2D::new(x, y - 1), Direction::South)),
+        Direction::East if x < grid[y].len() - 1 => {
+            Some(Move::new(Coord2D::new(x + 1, y), Direction::West))
         }
-    }
-    if y < grid.len() - 1 {
-        if let Some(_) = grid[y + 1][x].connects(Direction::North) {
-            dirs.push(Direction::South);
-            starts.push(Move::new(Coord2D::new(x, y + 1), Direction::North));
+        Direction::South if y < grid.len() - 1 => {
+            Some(Move::new(Coord2D::new(x, y + 1), Direction::North))
         }
+        Direction::West if x > 0 => Some(Move::new(Coord2D::new(x - 1, y), Direction::East)),
+        _ => None,
     }
-    // update start pipe
-    grid[y][x].update(dirs[0], dirs[1]);
-    (starts.pop().unwrap(), starts.pop().unwrap())
 }
 
-fn next_step(grid: &Grid, m: Move) -> Move {
+// directions from the start tile into a neighbour that connects back to it
+fn find_starts(grid: &Grid, start: Coord) -> Vec<Direction> {
+    [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ]
+    .into_iter()
+    .filter(|d| match step(grid, start, *d) {
+        Some(m) => grid[m.c.y][m.c.x].connects(m.came_from).is_some(),
+        None => false,
+    })
+    .collect()
+}
+
+fn next_step(grid: &Grid, m: Move) -> Option<Move> {
     let Coord2D { x, y } = m.c;
-    let d = grid[y][x].connects(m.came_from).unwrap();
-    match d {
-        Direction::North => Move::new(Coord2D { x, y: y - 1 }, Direction::South),
-        Direction::East => Move::new(Coord2D { x: x + 1, y }, Direction::West),
-        Direction::South => Move::new(Coord2D { x, y: y + 1 }, Direction::North),
-        Direction::West => Move::new(Coord2D { x: x - 1, y }, Direction::East),
-        Direction::Void => unreachable!("invalid direction"),
+    let d = grid[y][x].connects(m.came_from)?;
+    step(grid, m.c, d)
+}
+
+// walk out via d1 and expect to come back in via d2
+fn try_loop(grid: &Grid, start: Coord, d1: Direction, d2: Direction) -> Option<Vec<Coord>> {
+    let mut pipe = vec![start];
+    let mut m = step(grid, start, d1)?;
+    while m.c != start {
+        pipe.push(m.c);
+        m = next_step(grid, m)?;
+    }
+    if m.came_from == d2 {
+        Some(pipe)
+    } else {
+        None
     }
 }
 
-fn follow_pipe(grid: &mut Grid, start: Coord) -> HashSet<Coord> {
-    let mut pipe = HashSet::new();
-    pipe.insert(start.clone());
-    let (mut m1, mut m2) = find_starts(grid, start);
-    while m1.c != m2.c {
-        pipe.insert(m1.c);
-        pipe.insert(m2.c);
-        m1 = next_step(&grid, m1);
-        m2 = next_step(&grid, m2);
+// the loop's tiles in walking order, starting at start
+fn follow_pipe(grid: &mut Grid, start: Coord) -> Result<Vec<Coord>, LoopError> {
+    let dirs = find_starts(grid, start);
+    if dirs.len() < 2 {
+        return Err(LoopError::TooFewConnections(dirs.len()));
+    }
+    for (i, d1) in dirs.iter().enumerate() {
+        for d2 in dirs[i + 1..].iter() {
+            // the start tile has to connect both ways for the walk back in
+            grid[start.y][start.x].update(*d1, *d2);
+            if let Some(pipe) = try_loop(grid, start, *d1, *d2) {
+                return Ok(pipe);
+            }
+        }
     }
-    pipe.insert(m1.c);
-    pipe
+    grid[start.y][start.x].update(Direction::Void, Direction::Void);
+    Err(LoopError::NotClosed(dirs.len()))
 }
 
 fn scaled_pipe(pipe: &HashSet<Coord>, grid: &Grid) -> HashSet<Coord> {
@@ -234,21 +268,26 @@ fn flood_fill(coord: Coord, grid: &Grid, pipe: &HashSet<Coord>, outside: &mut Ha
     }
 }
 
-pub fn part1() -> usize {
-    let (start, mut grid) = input();
-    (follow_pipe(&mut grid, start).len() + 1) / 2
+// shoelace formula for the area, Pick's theorem for the interior points
+fn inside_by_shoelace(pipe: &[Coord]) -> usize {
+    let twice_area = pipe
+        .iter()
+        .zip(pipe.iter().cycle().skip(1))
+        .map(|(a, b)| (a.x * b.y) as i64 - (b.x * a.y) as i64)
+        .sum::<i64>()
+        .unsigned_abs() as usize;
+    (twice_area - pipe.len()) / 2 + 1
 }
 
-pub fn part2() -> usize {
-    let (start, mut grid) = input();
+fn inside_by_flood_fill(pipe: &[Coord], grid: &Grid) -> usize {
+    let pipe: HashSet<Coord> = pipe.iter().copied().collect();
     let mut outside: HashSet<Coord> = HashSet::new();
     // flood fill (scale 3x3 times to have gaps between pipes for easy filling)
-    let pipe = follow_pipe(&mut grid, start);
-    let scaled_pipe = scaled_pipe(&pipe, &grid);
-    (0..grid.len() * 3).into_iter().for_each(|y| {
-        (0..grid[0].len() * 3).into_iter().for_each(|x| {
+    let scaled_pipe = scaled_pipe(&pipe, grid);
+    (0..grid.len() * 3).for_each(|y| {
+        (0..grid[0].len() * 3).for_each(|x| {
             let c = Coord2D { x, y };
-            flood_fill(c, &grid, &scaled_pipe, &mut outside);
+            flood_fill(c, grid, &scaled_pipe, &mut outside);
         })
     });
     // non-scaled outside
@@ -260,33 +299,224 @@ pub fn part2() -> usize {
             }
         }
     }
-    // visual dump
-    let inside = grid.len() * grid[0].len() - (pipe.len() + outside_cnt);
-    // println!("Pipe {}/{}", pipe.len(), grid.len() * grid[0].len());
-    // println!("Out  {}/{}", outside_cnt, grid.len() * grid[0].len());
-    // println!("In   {}/{}", inside, grid.len() * grid[0].len());
-    // (0..grid.len() * 3).into_iter().for_each(|y| {
-    //     (0..grid[0].len() * 3).into_iter().for_each(|x| {
-    //         let t = &grid[y / 3][x / 3];
-    //         let c = Coord2D { x, y };
-    //         if scaled_pipe.contains(&c) {
-    //             print!("{}", t.pipe());
-    //         } else if outside.contains(&c) {
-    //             print!("o");
-    //         } else {
-    //             print!("?");
-    //         }
-    //     });
-    //     println!();
-    // });
-    // return the result
-    inside
+    grid.len() * grid[0].len() - (pipe.len() + outside_cnt)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum InsideCount {
+    Shoelace,
+    FloodFill,
+}
+
+pub fn count_inside(method: InsideCount) -> Result<usize, LoopError> {
+    let (start, mut grid) = input();
+    let pipe = follow_pipe(&mut grid, start)?;
+    Ok(match method {
+        InsideCount::Shoelace => inside_by_shoelace(&pipe),
+        InsideCount::FloodFill => inside_by_flood_fill(&pipe, &grid),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Render {
+    // ANSI colours: bold loop, dimmed stray pipes, coloured inside/outside
+    Terminal,
+    // loop as box-drawing characters, everything else 'I' or 'O'
+    PlainText,
+}
+
+// scanline parity: every loop tile with a northern connection is a crossing
+fn inside_tiles(pipe: &[Coord], grid: &Grid) -> Vec<Vec<bool>> {
+    let on_loop: HashSet<&Coord> = pipe.iter().collect();
+    grid.iter()
+        .enumerate()
+        .map(|(y, row)| {
+            let mut inside = false;
+            row.iter()
+                .enumerate()
+                .map(|(x, t)| {
+                    if on_loop.contains(&Coord2D::new(x, y)) {
+                        if t.connects(Direction::North).is_some() {
+                            inside = !inside;
+                        }
+                        false
+                    } else {
+                        inside
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn render_grid<W: Write>(
+    out: &mut W,
+    grid: &Grid,
+    pipe: &[Coord],
+    style: Render,
+) -> io::Result<()> {
+    const BOLD: &str = "\x1b[1m";
+    const DIM: &str = "\x1b[2m";
+    const INSIDE: &str = "\x1b[42m";
+    const OUTSIDE: &str = "\x1b[44m";
+    const RESET: &str = "\x1b[0m";
+    let on_loop: HashSet<&Coord> = pipe.iter().collect();
+    let inside = inside_tiles(pipe, grid);
+    for (y, row) in grid.iter().enumerate() {
+        for (x, t) in row.iter().enumerate() {
+            let is_loop = on_loop.contains(&Coord2D::new(x, y));
+            match style {
+                Render::PlainText if is_loop => write!(out, "{}", t.box_char())?,
+                Render::PlainText => write!(out, "{}", if inside[y][x] { 'I' } else { 'O' })?,
+                Render::Terminal if is_loop => write!(out, "{}{}{}", BOLD, t.box_char(), RESET)?,
+                Render::Terminal => {
+                    let bg = if inside[y][x] { INSIDE } else { OUTSIDE };
+                    if t.is_pipe() {
+                        write!(out, "{}{}{}{}", bg, DIM, t.box_char(), RESET)?
+                    } else {
+                        write!(
+                            out,
+                            "{}{}{}",
+                            bg,
+                            if inside[y][x] { 'I' } else { 'O' },
+                            RESET
+                        )?
+                    }
+                }
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+pub fn render<W: Write>(out: &mut W, style: Render) -> io::Result<()> {
+    let (start, mut grid) = input();
+    let pipe =
+        follow_pipe(&mut grid, start).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    render_grid(out, &grid, &pipe, style)
+}
+
+pub fn render_to_file(filename: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(filename)?);
+    render(&mut out, Render::PlainText)?;
+    out.flush()
+}
+
+pub fn part1() -> usize {
+    let (start, mut grid) = input();
+    follow_pipe(&mut grid, start)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .len()
+        / 2
+}
+
+pub fn part2() -> usize {
+    count_inside(InsideCount::Shoelace).unwrap_or_else(|e| panic!("{}", e))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn inside_both_ways(lines: &[&str]) -> (usize, usize) {
+        let (start, mut grid) = parse(lines);
+        let pipe = follow_pipe(&mut grid, start).unwrap();
+        (
+            inside_by_shoelace(&pipe),
+            inside_by_flood_fill(&pipe, &grid),
+        )
+    }
+
+    #[test]
+    fn test_inside() {
+        let squeezed = [
+            "..........",
+            ".S------7.",
+            ".|F----7|.",
+            ".||....||.",
+            ".||....||.",
+            ".|L-7F-J|.",
+            ".|..||..|.",
+            ".L--JL--J.",
+            "..........",
+        ];
+        assert_eq!(inside_both_ways(&squeezed), (4, 4));
+        let larger = [
+            ".F----7F7F7F7F-7....",
+            ".|F--7||||||||FJ....",
+            ".||.FJ||||||||L7....",
+            "FJL7L7LJLJ||LJ.L-7..",
+            "L--J.L7...LJS7F-7L7.",
+            "....F-J..F7FJ|L7L7L7",
+            "....L7.F7||L7|.L7L7|",
+            ".....|FJLJ|FJ|F7|.LJ",
+            "....FJL-7.||.||||...",
+            "....L---J.LJ.LJLJ...",
+        ];
+        assert_eq!(inside_both_ways(&larger), (8, 8));
+    }
+
+    #[test]
+    fn test_render() {
+        let (start, mut grid) = parse(&[
+            "..........",
+            ".S------7.",
+            ".|F----7|.",
+            ".||.|..||.",
+            ".||....||.",
+            ".|L-7F-J|.",
+            ".|..||..|.",
+            ".L--JL--J.",
+            "..........",
+        ]);
+        let pipe = follow_pipe(&mut grid, start).unwrap();
+        let mut out = vec![];
+        render_grid(&mut out, &grid, &pipe, Render::PlainText).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "OOOOOOOOOO\n\
+             O┌──────┐O\n\
+             O│┌────┐│O\n\
+             O││OOOO││O\n\
+             O││OOOO││O\n\
+             O│└─┐┌─┘│O\n\
+             O│II││II│O\n\
+             O└──┘└──┘O\n\
+             OOOOOOOOOO\n"
+        );
+        let mut out = vec![];
+        render_grid(&mut out, &grid, &pipe, Render::Terminal).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\x1b[44m\x1b[2m│\x1b[0m"));
+        assert!(out.contains("\x1b[42mI\x1b[0m"));
+    }
+
+    #[test]
+    fn test_ambiguous_start() {
+        // all four neighbours point at S, only east and south close the loop
+        let (start, mut grid) = parse(&[".....", "..|..", ".-S7.", "..LJ.", "....."]);
+        let pipe = follow_pipe(&mut grid, start).unwrap();
+        assert_eq!(pipe.len(), 4);
+        assert_eq!(grid[2][2].box_char(), '┌');
+    }
+
+    #[test]
+    fn test_invalid_start() {
+        let (start, mut grid) = parse(&[".....", ".S-7.", ".|.|.", ".L-..", "....."]);
+        assert_eq!(follow_pipe(&mut grid, start), Err(LoopError::NotClosed(2)));
+        let (start, mut grid) = parse(&["...", "S-.", "..."]);
+        assert_eq!(
+            follow_pipe(&mut grid, start),
+            Err(LoopError::TooFewConnections(1))
+        );
+        let err = io::Error::new(io::ErrorKind::InvalidData, LoopError::NotClosed(2));
+        assert_eq!(
+            err.to_string(),
+            "None of the 2 connecting neighbours of the start tile form a closed loop"
+        );
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 6815);
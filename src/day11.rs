@@ -1,19 +1,29 @@
-use itertools::Itertools;
 use std::collections::HashSet;
 
-use crate::util::{Coord2D, Grid};
+use crate::util::{load, Coord2D};
 
 type Coord = Coord2D<usize>;
-type Galaxies = HashSet<Coord>;
 
-fn input() -> Galaxies {
-    Grid::from_file("data/day11.txt", |c, _| if c == '#' { Some(()) } else { None })
-        .squares
-        .keys()
-        .cloned()
+fn input() -> Vec<Coord> {
+    parse(&load::<String>("data/day11.txt"))
+}
+
+fn parse<S: AsRef<str>>(lines: &[S]) -> Vec<Coord> {
+    lines
+        .iter()
+        .enumerate()
+        .flat_map(|(y, l)| {
+            l.as_ref()
+                .chars()
+                .enumerate()
+                .filter(|(_, c)| *c == '#')
+                .map(move |(x, _)| Coord2D::new(x, y))
+                .collect::<Vec<_>>()
+        })
         .collect()
 }
 
+// extra space before each row/column index
 fn calc_expansion(nums: HashSet<usize>, factor: usize) -> Vec<usize> {
     (0..=*nums.iter().max().unwrap())
         .fold((0, vec![]), |(exp, mut acc), i| {
@@ -23,47 +33,108 @@ fn calc_expansion(nums: HashSet<usize>, factor: usize) -> Vec<usize> {
         .1
 }
 
-fn manhatten_dist(c1: &Coord, c2: &Coord) -> usize {
-    c1.x.abs_diff(c2.x) + c1.y.abs_diff(c2.y)
+// sum of |a - b| over all pairs: after sorting, value i is larger than
+// the i values before it
+fn pairwise_sum(mut nums: Vec<usize>) -> usize {
+    nums.sort_unstable();
+    nums.into_iter()
+        .enumerate()
+        .fold((0, 0), |(sum, prefix), (i, n)| (sum + n * i - prefix, prefix + n))
+        .0
 }
 
-fn expanded_distance(g1: Coord, g2: Coord, x_exp: &Vec<usize>, y_exp: &Vec<usize>) -> usize {
-    manhatten_dist(&g1, &g2)
-        + manhatten_dist(
-            &Coord {
-                x: x_exp[g1.x],
-                y: y_exp[g1.y],
-            },
-            &Coord {
-                x: x_exp[g2.x],
-                y: y_exp[g2.y],
-            },
-        )
+pub struct Universe {
+    // galaxies in reading order, as observed
+    galaxies: Vec<Coord>,
+    x_exp: Vec<usize>,
+    y_exp: Vec<usize>,
 }
 
-fn distance_sum(factor: usize) -> usize {
-    let galaxies = input();
-    let x_exp = calc_expansion(galaxies.iter().map(|c| c.x).collect(), factor);
-    let y_exp = calc_expansion(galaxies.iter().map(|c| c.y).collect(), factor);
-    galaxies
-        .into_iter()
-        .combinations(2)
-        .map(|g| expanded_distance(g[0], g[1], &x_exp, &y_exp))
-        .sum()
+impl Universe {
+    // every empty column becomes x_factor columns, every empty row y_factor rows
+    pub fn new(mut galaxies: Vec<Coord>, x_factor: usize, y_factor: usize) -> Self {
+        galaxies.sort_by_key(|c| (c.y, c.x));
+        let x_exp = calc_expansion(galaxies.iter().map(|c| c.x).collect(), x_factor - 1);
+        let y_exp = calc_expansion(galaxies.iter().map(|c| c.y).collect(), y_factor - 1);
+        Universe {
+            galaxies,
+            x_exp,
+            y_exp,
+        }
+    }
+
+    pub fn galaxies(&self) -> &[Coord] {
+        &self.galaxies
+    }
+
+    fn expanded(&self, c: &Coord) -> Coord {
+        Coord2D::new(c.x + self.x_exp[c.x], c.y + self.y_exp[c.y])
+    }
+
+    pub fn distance(&self, g1: &Coord, g2: &Coord) -> usize {
+        let (e1, e2) = (self.expanded(g1), self.expanded(g2));
+        e1.x.abs_diff(e2.x) + e1.y.abs_diff(e2.y)
+    }
+
+    // manhattan distances are independent per axis
+    pub fn distance_sum(&self) -> usize {
+        let expanded: Vec<Coord> = self.galaxies.iter().map(|g| self.expanded(g)).collect();
+        pairwise_sum(expanded.iter().map(|c| c.x).collect())
+            + pairwise_sum(expanded.iter().map(|c| c.y).collect())
+    }
 }
 
 pub fn part1() -> usize {
-    distance_sum(1)
+    Universe::new(input(), 2, 2).distance_sum()
 }
 
 pub fn part2() -> usize {
-    distance_sum(1_000_000 - 1)
+    Universe::new(input(), 1_000_000, 1_000_000).distance_sum()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const SAMPLE: [&str; 10] = [
+        "...#......",
+        ".......#..",
+        "#.........",
+        "..........",
+        "......#...",
+        ".#........",
+        ".........#",
+        "..........",
+        ".......#..",
+        "#...#.....",
+    ];
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(Universe::new(parse(&SAMPLE), 2, 2).distance_sum(), 374);
+        assert_eq!(Universe::new(parse(&SAMPLE), 10, 10).distance_sum(), 1030);
+        assert_eq!(Universe::new(parse(&SAMPLE), 100, 100).distance_sum(), 8410);
+        let u = Universe::new(parse(&SAMPLE), 2, 2);
+        let g = u.galaxies();
+        assert_eq!(u.distance(&g[4], &g[8]), 9);
+        assert_eq!(u.distance(&g[0], &g[6]), 15);
+        assert_eq!(u.distance(&g[7], &g[8]), 5);
+    }
+
+    #[test]
+    fn test_independent_factors() {
+        let galaxies = parse(&SAMPLE);
+        let u = Universe::new(galaxies.clone(), 3, 1);
+        let brute: usize = (0..galaxies.len())
+            .flat_map(|i| (i + 1..galaxies.len()).map(move |j| (i, j)))
+            .map(|(i, j)| u.distance(&u.galaxies()[i], &u.galaxies()[j]))
+            .sum();
+        assert_eq!(u.distance_sum(), brute);
+        // empty columns 2, 5 and 8 triple, empty row 3 stays as is
+        let g = u.galaxies();
+        assert_eq!(u.distance(&g[2], &g[5]), (9 + 3 * 2) + 4);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 10173804);
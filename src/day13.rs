@@ -1,95 +1,222 @@
-use crate::util::load;
+use crate::util::{load, Coord2D};
 
-#[derive(Debug)]
-struct Grid {
-    horz: Vec<u32>,
-    vert: Vec<u32>,
+type Coord = Coord2D<usize>;
+
+// fixed size bitset of any width
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bits(Vec<u64>);
+
+impl Bits {
+    fn new(len: usize) -> Self {
+        Bits(vec![0; len.div_ceil(64)])
+    }
+
+    fn set(&mut self, i: usize) {
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    fn count_differences(&self, other: &Bits) -> usize {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a ^ b).count_ones() as usize)
+            .sum()
+    }
+
+    fn differences<'a>(&'a self, other: &'a Bits) -> impl Iterator<Item = usize> + 'a {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .enumerate()
+            .flat_map(|(w, (a, b))| {
+                let diff = a ^ b;
+                (0..64)
+                    .filter(move |i| diff & (1 << i) != 0)
+                    .map(move |i| w * 64 + i)
+            })
+    }
 }
 
-fn input() -> Vec<Grid> {
-    let mut grids = vec![];
-    let last = load::<String>("data/day13.txt").into_iter().fold(
-        (vec![], vec![]),
-        |(mut horz, vert), l| {
-            if l == "" {
-                grids.push(Grid { horz, vert });
-                (vec![], vec![])
-            } else {
-                let (h, v) = l
-                    .chars()
-                    .enumerate()
-                    .fold((0u32, vert), |(mut h, mut v), (i, c)| {
-                        if i >= v.len() {
-                            v.push(0);
-                        }
-                        h <<= 1;
-                        v[i] <<= 1;
-                        if c == '#' {
-                            h |= 1;
-                            v[i] |= 1
-                        }
-                        (h, v)
-                    });
-                horz.push(h);
-                (horz, v)
-            }
-        },
-    );
-    grids.push(Grid {
-        horz: last.0,
-        vert: last.1,
-    });
-    grids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    // line between two rows
+    Horizontal,
+    // line between two columns
+    Vertical,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reflection {
+    pub axis: Axis,
+    // number of rows above or columns left of the line
+    pub index: usize,
+    // cells that do not match their mirror image
+    pub differences: usize,
+    // cell to flip to make a single-difference line a perfect mirror
+    pub smudge: Option<Coord>,
 }
 
-fn find_mirror(n: Vec<u32>, part2: bool) -> usize {
-    fn num_different_bits(a: u32, b: u32) -> u32 {
-        let mut n = a ^ b;
-        let mut nz = 0;
-        while n > 0 {
-            nz += n & 1;
-            n >>= 1;
+impl Reflection {
+    pub fn summary(&self) -> usize {
+        match self.axis {
+            Axis::Horizontal => 100 * self.index,
+            Axis::Vertical => self.index,
         }
-        nz
     }
+}
 
-    let mut prev = n[0];
-    for i in 1..n.len() {
-        let mut nzb = num_different_bits(prev, n[i]);
-        if nzb <= 1 {
-            for j in 0..(i - 1) {
-                if 2 * i - j - 1 < n.len() {
-                    nzb += num_different_bits(n[j], n[2 * i - j - 1]);
-                    if nzb > 1 {
-                        break;
-                    }
+#[derive(Debug)]
+pub struct Pattern {
+    rows: Vec<Bits>,
+    cols: Vec<Bits>,
+}
+
+impl Pattern {
+    pub fn new<S: AsRef<str>>(lines: &[S]) -> Self {
+        let width = lines[0].as_ref().len();
+        let mut rows = vec![Bits::new(width); lines.len()];
+        let mut cols = vec![Bits::new(lines.len()); width];
+        for (y, l) in lines.iter().enumerate() {
+            for (x, c) in l.as_ref().chars().enumerate() {
+                if c == '#' {
+                    rows[y].set(x);
+                    cols[x].set(y);
                 }
             }
-            if (!part2 && nzb == 0) || (part2 && nzb == 1) {
-                return i;
-            }
         }
-        prev = n[i]
+        Pattern { rows, cols }
     }
-    0 // no mirror found
+
+    fn lines(lanes: &[Bits], axis: Axis, max_differences: usize) -> Vec<Reflection> {
+        (1..lanes.len())
+            .filter_map(|i| {
+                let pairs = (0..i.min(lanes.len() - i)).map(|k| (i - 1 - k, i + k));
+                let mut differences = 0;
+                let mut smudge = None;
+                for (a, b) in pairs {
+                    differences += lanes[a].count_differences(&lanes[b]);
+                    if differences > max_differences {
+                        return None;
+                    }
+                    if let Some(d) = lanes[a].differences(&lanes[b]).next() {
+                        smudge = Some(match axis {
+                            Axis::Horizontal => Coord2D::new(d, a),
+                            Axis::Vertical => Coord2D::new(a, d),
+                        });
+                    }
+                }
+                Some(Reflection {
+                    axis,
+                    index: i,
+                    differences,
+                    smudge: if differences == 1 { smudge } else { None },
+                })
+            })
+            .collect()
+    }
+
+    // all reflection lines with at most max_differences mismatching cells
+    pub fn reflections(&self, max_differences: usize) -> Vec<Reflection> {
+        let mut result = Pattern::lines(&self.rows, Axis::Horizontal, max_differences);
+        result.extend(Pattern::lines(&self.cols, Axis::Vertical, max_differences));
+        result
+    }
+}
+
+fn parse<S: AsRef<str>>(lines: &[S]) -> Vec<Pattern> {
+    lines
+        .split(|l| l.as_ref().is_empty())
+        .filter(|block| !block.is_empty())
+        .map(Pattern::new)
+        .collect()
 }
 
-fn find_mirrors(grid: Grid, part2: bool) -> usize {
-    100 * find_mirror(grid.horz, part2) + find_mirror(grid.vert, part2)
+fn input() -> Vec<Pattern> {
+    parse(&load::<String>("data/day13.txt"))
+}
+
+fn summarize(patterns: &[Pattern], differences: usize) -> usize {
+    patterns
+        .iter()
+        .flat_map(|p| p.reflections(differences))
+        .filter(|r| r.differences == differences)
+        .map(|r| r.summary())
+        .sum()
 }
 
 pub fn part1() -> usize {
-    input().into_iter().map(|g| find_mirrors(g, false)).sum()
+    summarize(&input(), 0)
 }
 
 pub fn part2() -> usize {
-    input().into_iter().map(|g| find_mirrors(g, true)).sum()
+    summarize(&input(), 1)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const SAMPLE: [&str; 7] = [
+        "#...##..#",
+        "#....#..#",
+        "..##..###",
+        "#####.##.",
+        "#####.##.",
+        "..##..###",
+        "#....#..#",
+    ];
+
+    #[test]
+    fn test_sample() {
+        let patterns = parse(&SAMPLE);
+        assert_eq!(summarize(&patterns, 0), 400);
+        assert_eq!(summarize(&patterns, 1), 100);
+        assert_eq!(
+            patterns[0].reflections(1),
+            vec![
+                Reflection {
+                    axis: Axis::Horizontal,
+                    index: 1,
+                    differences: 1,
+                    smudge: Some(Coord2D::new(4, 0)),
+                },
+                Reflection {
+                    axis: Axis::Horizontal,
+                    index: 4,
+                    differences: 0,
+                    smudge: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wide_pattern() {
+        // 100 columns, mirrored between columns 69 and 70
+        let row: String = (0..100)
+            .map(|x: usize| match x.min(139 - x) * 7 % 3 {
+                0 => '#',
+                _ => '.',
+            })
+            .collect();
+        let mut smudged = row.clone();
+        smudged.replace_range(50..51, if &row[50..51] == "#" { "." } else { "#" });
+        let patterns = parse(&[row.as_str(), smudged.as_str()]);
+        let reflections = patterns[0].reflections(1);
+        assert!(reflections.contains(&Reflection {
+            axis: Axis::Vertical,
+            index: 70,
+            differences: 1,
+            smudge: Some(Coord2D::new(50, 1)),
+        }));
+        assert!(reflections.contains(&Reflection {
+            axis: Axis::Horizontal,
+            index: 1,
+            differences: 1,
+            smudge: Some(Coord2D::new(50, 0)),
+        }));
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 27202);
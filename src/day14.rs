@@ -1,169 +1,215 @@
-use std::{collections::HashMap, fmt::Display};
+use std::fmt::{Display, Formatter};
 
-use itertools::Itertools;
+use crate::util::{find_cycle, load};
 
-use crate::util::{Coord2D, Grid};
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
 
-#[derive(Debug, PartialEq, Eq)]
-enum Square {
-    FIXED,
-    MOVING,
+// e.g. "NWSE" for a spin cycle
+pub fn tilts(s: &str) -> Vec<Direction> {
+    s.chars()
+        .map(|c| match c {
+            'N' => Direction::North,
+            'E' => Direction::East,
+            'S' => Direction::South,
+            'W' => Direction::West,
+            _ => unreachable!("Invalid tilt '{}'", c),
+        })
+        .collect()
 }
 
-fn input() -> Grid<usize, Square> {
-    Grid::from_file("data/day14.txt", |c, _| match c {
-        '.' => None,
-        '#' => Some(Square::FIXED),
-        'O' => Some(Square::MOVING),
-        _ => unreachable!("Invalid char '{}'", c),
-    })
+// One bit per column, one word per row. Only the rolling rocks change, so
+// they double as a compact, hashable state.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct State(Vec<u128>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    width: usize,
+    height: usize,
+    fixed: Vec<u128>,
+    rolling: State,
 }
 
-impl Grid<usize, Square> {
-    fn calc_load(&self) -> usize {
-        let mut load = 0;
-        for x in 0..self.width {
-            for y in 0..self.height {
-                if let Some(Square::MOVING) = self.squares.get(&Coord2D::new(x, y)) {
-                    load += self.height - y;
-                }
+#[derive(Debug, PartialEq)]
+pub enum PlatformError {
+    // no rows, or rows without columns
+    Empty,
+    // a row is longer than the 128 columns a row word holds
+    TooWide(usize),
+    // index of a row whose length differs from the first row
+    Ragged(usize),
+}
+
+impl Display for PlatformError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlatformError::Empty => write!(f, "Platform has no squares"),
+            PlatformError::TooWide(w) => {
+                write!(
+                    f,
+                    "Platform is {} columns wide, at most 128 are supported",
+                    w
+                )
+            }
+            PlatformError::Ragged(y) => {
+                write!(f, "Row {} differs in length from the first row", y)
             }
         }
-        load
     }
+}
 
-    fn key(&self) -> String {
-        self.squares
-            .iter()
-            .filter(|(_, v)| **v == Square::MOVING)
-            .map(|(k, _)| k.y * 100 + k.x)
-            .sorted()
-            .map(|n| format!("{:04}", n))
-            .collect()
-    }
-
-    fn find_next_empty_space(&self, x: usize, y: usize, dx: i32, dy: i32) -> (usize, usize) {
-        let mut nx = x as i32 + dx;
-        let mut ny = y as i32 + dy;
-        while self.squares.contains_key(&Coord2D {
-            x: nx as usize,
-            y: ny as usize,
-        }) {
-            nx += dx;
-            ny += dy;
+impl std::error::Error for PlatformError {}
+
+fn input() -> Platform {
+    Platform::new(&load::<String>("data/day14.txt")).unwrap_or_else(|e| panic!("{}", e))
+}
+
+impl Platform {
+    // each row is a single u128, so platforms can be at most 128 columns wide
+    pub fn new<S: AsRef<str>>(lines: &[S]) -> Result<Self, PlatformError> {
+        let width = lines.first().map_or(0, |l| l.as_ref().len());
+        if width == 0 {
+            return Err(PlatformError::Empty);
+        }
+        if width > 128 {
+            return Err(PlatformError::TooWide(width));
         }
-        (nx as usize, ny as usize)
-    }
-
-    fn tilt_north(&mut self) {
-        let mut coord = Coord2D::new(0, 0);
-        for x in 0..self.width {
-            coord.x = x;
-            let mut new_y = 0;
-            for y in 0..self.height {
-                coord.y = y;
-                if let Some(s) = self.squares.get(&coord) {
-                    match s {
-                        Square::MOVING if y != new_y => {
-                            self.squares.remove(&coord);
-                            self.squares.insert(Coord2D::new(x, new_y), Square::MOVING);
-                            new_y = self.find_next_empty_space(x, new_y, 0, 1).1;
-                        }
-                        _ => new_y = y + 1,
-                    }
+        if let Some(y) = lines.iter().position(|l| l.as_ref().len() != width) {
+            return Err(PlatformError::Ragged(y));
+        }
+        let mut fixed = vec![0; lines.len()];
+        let mut rolling = vec![0; lines.len()];
+        for (y, l) in lines.iter().enumerate() {
+            for (x, c) in l.as_ref().chars().enumerate() {
+                match c {
+                    '.' => {}
+                    '#' => fixed[y] |= 1 << x,
+                    'O' => rolling[y] |= 1 << x,
+                    _ => unreachable!("Invalid char '{}'", c),
                 }
             }
         }
+        Ok(Platform {
+            width,
+            height: lines.len(),
+            fixed,
+            rolling: State(rolling),
+        })
+    }
+
+    fn mask(&self) -> u128 {
+        u128::MAX >> (128 - self.width)
+    }
+
+    fn free(&self, y: usize) -> u128 {
+        !(self.fixed[y] | self.rolling.0[y]) & self.mask()
+    }
+
+    // every rock weighs its distance to the given edge, counting its own row/column
+    pub fn calc_load(&self, edge: Direction) -> usize {
+        let rows = self.rolling.0.iter().enumerate();
+        match edge {
+            Direction::North => rows
+                .map(|(y, row)| row.count_ones() as usize * (self.height - y))
+                .sum(),
+            Direction::South => rows
+                .map(|(y, row)| row.count_ones() as usize * (y + 1))
+                .sum(),
+            Direction::West => (0..self.width)
+                .map(|x| self.column_count(x) * (self.width - x))
+                .sum(),
+            Direction::East => (0..self.width)
+                .map(|x| self.column_count(x) * (x + 1))
+                .sum(),
+        }
+    }
+
+    fn column_count(&self, x: usize) -> usize {
+        self.rolling
+            .0
+            .iter()
+            .filter(|row| *row & (1 << x) != 0)
+            .count()
     }
 
-    fn tilt_south(&mut self) {
-        let mut coord = Coord2D::new(0, 0);
-        for x in 0..self.width {
-            coord.x = x;
-            let mut new_y = self.height - 1;
-            for y in (0..self.height).rev() {
-                coord.y = y;
-                if let Some(s) = self.squares.get(&coord) {
-                    match s {
-                        Square::MOVING if y != new_y => {
-                            self.squares.remove(&coord);
-                            self.squares.insert(Coord2D::new(x, new_y), Square::MOVING);
-                            new_y = self.find_next_empty_space(x, new_y, 0, -1).1;
-                        }
-                        _ => {
-                            if y > 0 {
-                                new_y = y - 1;
-                            }
-                        }
-                    }
+    // move all rocks that can one row up/down at once, until none can
+    fn tilt_vertical(&mut self, rows: Vec<(usize, usize)>) {
+        loop {
+            let mut moved = false;
+            for (from, to) in rows.iter() {
+                let movers = self.rolling.0[*from] & self.free(*to);
+                if movers != 0 {
+                    self.rolling.0[*from] &= !movers;
+                    self.rolling.0[*to] |= movers;
+                    moved = true;
                 }
             }
+            if !moved {
+                break;
+            }
         }
     }
 
-    fn tilt_west(&mut self) {
-        let mut coord = Coord2D::new(0, 0);
+    pub fn tilt_north(&mut self) {
+        self.tilt_vertical((1..self.height).map(|y| (y, y - 1)).collect());
+    }
+
+    pub fn tilt_south(&mut self) {
+        self.tilt_vertical((0..self.height - 1).rev().map(|y| (y, y + 1)).collect());
+    }
+
+    pub fn tilt_west(&mut self) {
         for y in 0..self.height {
-            coord.y = y;
-            let mut new_x = 0;
-            for x in 0..self.width {
-                coord.x = x;
-                if let Some(s) = self.squares.get(&coord) {
-                    match s {
-                        Square::MOVING if x != new_x => {
-                            self.squares.remove(&coord);
-                            self.squares.insert(Coord2D::new(new_x, y), Square::MOVING);
-                            new_x = self.find_next_empty_space(new_x, y, 1, 0).0;
-                        }
-                        _ => new_x = x + 1,
-                    }
+            loop {
+                let movers = self.rolling.0[y] & (self.free(y) << 1);
+                if movers == 0 {
+                    break;
                 }
+                self.rolling.0[y] = (self.rolling.0[y] & !movers) | (movers >> 1);
             }
         }
     }
 
-    fn tilt_east(&mut self) {
-        let mut coord = Coord2D::new(0, 0);
+    pub fn tilt_east(&mut self) {
         for y in 0..self.height {
-            coord.y = y;
-            let mut new_x = self.width - 1;
-            for x in (0..self.width).rev() {
-                coord.x = x;
-                if let Some(s) = self.squares.get(&coord) {
-                    match s {
-                        Square::MOVING if x != new_x => {
-                            self.squares.remove(&coord);
-                            self.squares.insert(Coord2D::new(new_x, y), Square::MOVING);
-                            new_x = self.find_next_empty_space(new_x, y, -1, 0).0;
-                        }
-                        _ => {
-                            if x > 0 {
-                                new_x = x - 1;
-                            }
-                        }
-                    }
+            loop {
+                let movers = self.rolling.0[y] & (self.free(y) >> 1);
+                if movers == 0 {
+                    break;
                 }
+                self.rolling.0[y] = (self.rolling.0[y] & !movers) | (movers << 1);
             }
         }
     }
 
-    fn cycle(&mut self) {
-        self.tilt_north();
-        self.tilt_west();
-        self.tilt_south();
-        self.tilt_east();
+    pub fn tilt(&mut self, d: Direction) {
+        match d {
+            Direction::North => self.tilt_north(),
+            Direction::East => self.tilt_east(),
+            Direction::South => self.tilt_south(),
+            Direction::West => self.tilt_west(),
+        }
+    }
+
+    pub fn tilt_all(&mut self, sequence: &[Direction]) {
+        sequence.iter().for_each(|d| self.tilt(*d));
     }
 }
 
-impl Display for Grid<usize, Square> {
+impl Display for Platform {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for y in 0..self.height {
             for x in 0..self.width {
-                if let Some(s) = self.squares.get(&Coord2D::new(x, y)) {
-                    match s {
-                        Square::MOVING => write!(f, "O")?,
-                        Square::FIXED => write!(f, "#")?,
-                    }
+                if self.rolling.0[y] & (1 << x) != 0 {
+                    write!(f, "O")?;
+                } else if self.fixed[y] & (1 << x) != 0 {
+                    write!(f, "#")?;
                 } else {
                     write!(f, ".")?;
                 }
@@ -174,43 +220,104 @@ impl Display for Grid<usize, Square> {
     }
 }
 
+// run the tilt sequence the given number of times
+pub fn spin(platform: Platform, sequence: &[Direction], times: usize) -> Platform {
+    // cycle over the rolling rocks only, tilting them on one working platform
+    let initial = platform.rolling.clone();
+    let mut work = platform;
+    let cycle = find_cycle(initial, |s| {
+        work.rolling = s.clone();
+        work.tilt_all(sequence);
+        work.rolling.clone()
+    });
+    work.rolling = cycle.state_at(times).clone();
+    work
+}
+
 pub fn part1() -> usize {
-    let mut grid = input();
-    grid.tilt_north();
-    grid.calc_load()
+    let mut platform = input();
+    platform.tilt_north();
+    platform.calc_load(Direction::North)
 }
 
 pub fn part2() -> usize {
-    let cycles = 1_000_000_000;
-    let mut grid = input();
-    let mut visited: HashMap<String, usize> = HashMap::new();
-    let mut it = 0;
-    let period = loop {
-        if it == cycles {
-            unreachable!("Expected to exit early");
-        }
-        it += 1;
-        grid.cycle();
-        let key = grid.key();
-        if visited.contains_key(&key) {
-            // found repeat
-            break it - visited.get(&key).unwrap(); // period
-        }
-        visited.insert(key, it);
-    };
-    // skip a bunch of repeating cycles
-    let left = (cycles - it) % period;
-    // do the remainder
-    for _ in 0..left {
-        grid.cycle();
-    }
-    grid.calc_load()
+    spin(input(), &tilts("NWSE"), 1_000_000_000).calc_load(Direction::North)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const SAMPLE: [&str; 10] = [
+        "O....#....",
+        "O.OO#....#",
+        ".....##...",
+        "OO.#O....O",
+        ".O.....O#.",
+        "O.#..O.#.#",
+        "..O..#O..O",
+        ".......O..",
+        "#....###..",
+        "#OO..#....",
+    ];
+
+    #[test]
+    fn test_sample() {
+        let mut platform = Platform::new(&SAMPLE).unwrap();
+        platform.tilt_north();
+        assert_eq!(platform.calc_load(Direction::North), 136);
+        let mut platform = Platform::new(&SAMPLE).unwrap();
+        platform.tilt_all(&tilts("NWSE"));
+        assert_eq!(
+            platform.to_string(),
+            ".....#....\n\
+             ....#...O#\n\
+             ...OO##...\n\
+             .OO#......\n\
+             .....OOO#.\n\
+             .O#...O#.#\n\
+             ....O#....\n\
+             ......OOOO\n\
+             #...O###..\n\
+             #..OO#....\n"
+        );
+        let spun = spin(
+            Platform::new(&SAMPLE).unwrap(),
+            &tilts("NWSE"),
+            1_000_000_000,
+        );
+        assert_eq!(spun.calc_load(Direction::North), 64);
+    }
+
+    #[test]
+    fn test_tilt_sequences() {
+        let platform = Platform::new(&[".O.", "O#.", "..O"]).unwrap();
+        let spun = spin(platform.clone(), &tilts("NNE"), 1000);
+        assert_eq!(spun.to_string(), "OOO\n.#.\n...\n");
+        let spun = spin(platform.clone(), &tilts("ES"), 1000);
+        assert_eq!(spun.to_string(), "...\n.#O\n.OO\n");
+        assert_eq!(spun.calc_load(Direction::North), 2 + 2);
+        assert_eq!(spun.calc_load(Direction::South), 2 + 2 * 3);
+        assert_eq!(spun.calc_load(Direction::East), 2 * 3 + 2);
+        assert_eq!(spun.calc_load(Direction::West), 2 + 2);
+        let once = spin(platform, &tilts("SW"), 1);
+        assert_eq!(once.to_string(), "O..\n.#.\nOO.\n");
+        assert_eq!(once.calc_load(Direction::West), 2 * 3 + 2);
+    }
+
+    #[test]
+    fn test_invalid_platform() {
+        let empty: [&str; 0] = [];
+        assert_eq!(Platform::new(&empty), Err(PlatformError::Empty));
+        assert_eq!(Platform::new(&[""]), Err(PlatformError::Empty));
+        assert_eq!(
+            Platform::new(&["O".repeat(129)]),
+            Err(PlatformError::TooWide(129))
+        );
+        assert!(Platform::new(&["O".repeat(128)]).is_ok());
+        assert_eq!(Platform::new(&["O.", "O"]), Err(PlatformError::Ragged(1)));
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 109385);
@@ -1,12 +1,9 @@
-use std::collections::HashMap;
-
-use crate::util::load;
-
-struct HolidayAsciiStringHelper {
-    steps: Vec<String>,
-    boxes: Vec<Vec<String>>,
-    lenses: HashMap<String, u32>,
-}
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    str::FromStr,
+};
 
 fn hash(s: &str) -> u8 {
     s.as_bytes()
@@ -14,75 +11,232 @@ fn hash(s: &str) -> u8 {
         .fold(0u32, |v, c| (v + (*c as u32)) * 17 % 256) as u8
 }
 
-impl HolidayAsciiStringHelper {
-    fn load(file: &str) -> Self {
-        HolidayAsciiStringHelper {
-            steps: load::<String>(file)[0]
-                .split(",")
-                .map(|s| s.to_string())
-                .collect(),
+// Holiday ASCII String Helper Manual Arrangement Procedure: 256 boxes of
+// labelled slots, the box picked by the HASH of the label
+#[derive(Debug, Clone)]
+pub struct Hashmap<V> {
+    boxes: Vec<Vec<(String, V)>>,
+}
+
+impl<V> Default for Hashmap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> Hashmap<V> {
+    pub fn new() -> Self {
+        Hashmap {
             boxes: (0..256).map(|_| vec![]).collect(),
-            lenses: HashMap::new(),
         }
     }
 
-    fn calc_hash_sum(&self) -> usize {
-        self.steps.iter().map(|s| hash(s) as usize).sum()
+    fn slots(&self, label: &str) -> &Vec<(String, V)> {
+        &self.boxes[hash(label) as usize]
     }
 
-    fn init_lenses(&mut self) {
-        self.steps
-            .iter()
-            .map(|s| {
-                let mut tokens = s.split(['=', '-']);
-                (
-                    tokens.next().unwrap(),
-                    tokens.next().unwrap().parse::<usize>().ok(),
-                )
-            })
-            .map(|(lens, value)| (lens, value, hash(&lens)))
-            .for_each(|(lens, value, box_id)| match value {
-                Some(focal_length) => {
-                    // add lens
-                    self.lenses.insert(lens.to_string(), focal_length as u32);
-                    if self.boxes[box_id as usize].iter().all(|l| l != lens) {
-                        self.boxes[box_id as usize].push(lens.to_string());
-                    }
-                }
-                None => self.boxes[box_id as usize].retain(|l| l != lens), // remove lens
-            });
-    }
-
-    fn focusing_power(&self) -> usize {
-        self.boxes
+    // replace in place, or add to the back of the box
+    pub fn insert(&mut self, label: &str, value: V) -> Option<V> {
+        let slots = &mut self.boxes[hash(label) as usize];
+        match slots.iter_mut().find(|(l, _)| l == label) {
+            Some((_, v)) => Some(std::mem::replace(v, value)),
+            None => {
+                slots.push((label.to_string(), value));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, label: &str) -> Option<V> {
+        let slots = &mut self.boxes[hash(label) as usize];
+        let pos = slots.iter().position(|(l, _)| l == label)?;
+        Some(slots.remove(pos).1)
+    }
+
+    pub fn get(&self, label: &str) -> Option<&V> {
+        self.slots(label)
             .iter()
-            .enumerate()
-            .map(|(box_id, b)| {
-                b.iter()
-                    .enumerate()
-                    .map(|(lens_slot, label)| {
-                        (box_id + 1) * (lens_slot + 1) * *self.lenses.get(label).unwrap() as usize
-                    })
-                    .sum::<usize>()
+            .find(|(l, _)| l == label)
+            .map(|(_, v)| v)
+    }
+
+    pub fn len(&self) -> usize {
+        self.boxes.iter().map(|b| b.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.iter().all(|b| b.is_empty())
+    }
+
+    // (box, slot, label, value) in box and slot order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &str, &V)> {
+        self.boxes.iter().enumerate().flat_map(|(box_id, b)| {
+            b.iter()
+                .enumerate()
+                .map(move |(slot, (l, v))| (box_id, slot, l.as_str(), v))
+        })
+    }
+}
+
+impl Hashmap<u32> {
+    pub fn focusing_power(&self) -> usize {
+        self.iter()
+            .map(|(box_id, slot, _, focal_length)| {
+                (box_id + 1) * (slot + 1) * *focal_length as usize
             })
             .sum()
     }
 }
 
+// non-empty boxes as in the puzzle description, e.g. "Box 3: [ot 7] [ab 5]"
+impl<V: Display> Display for Hashmap<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (box_id, b) in self.boxes.iter().enumerate().filter(|(_, b)| !b.is_empty()) {
+            write!(f, "Box {}:", box_id)?;
+            for (label, v) in b.iter() {
+                write!(f, " [{} {}]", label, v)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Insert(String, u32),
+    Remove(String),
+}
+
+impl FromStr for Operation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((label, focal_length)) => Ok(Operation::Insert(
+                label.to_string(),
+                focal_length.parse().map_err(|_| ())?,
+            )),
+            None => match s.strip_suffix('-') {
+                Some(label) => Ok(Operation::Remove(label.to_string())),
+                None => Err(()),
+            },
+        }
+    }
+}
+
+impl Operation {
+    pub fn apply(&self, lenses: &mut Hashmap<u32>) {
+        match self {
+            Operation::Insert(label, focal_length) => {
+                lenses.insert(label, *focal_length);
+            }
+            Operation::Remove(label) => {
+                lenses.remove(label);
+            }
+        }
+    }
+}
+
+// comma separated steps, without reading the whole line at once
+pub fn steps<R: BufRead>(reader: R) -> impl Iterator<Item = String> {
+    reader
+        .split(b',')
+        .map(|s| String::from_utf8(s.unwrap()).unwrap().trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+// calls on_step with every step and the boxes right after it
+pub fn init_lenses_with<R, F>(reader: R, mut on_step: F) -> Hashmap<u32>
+where
+    R: BufRead,
+    F: FnMut(&str, &Hashmap<u32>),
+{
+    steps(reader).fold(Hashmap::new(), |mut lenses, s| {
+        s.parse::<Operation>().unwrap().apply(&mut lenses);
+        on_step(&s, &lenses);
+        lenses
+    })
+}
+
+pub fn init_lenses<R: BufRead>(reader: R) -> Hashmap<u32> {
+    init_lenses_with(reader, |_, _| {})
+}
+
+pub fn trace_lenses<R: BufRead, W: Write>(reader: R, out: &mut W) -> io::Result<Hashmap<u32>> {
+    let mut result = Ok(());
+    let lenses = init_lenses_with(reader, |s, lenses| {
+        if result.is_ok() {
+            result = writeln!(out, "After \"{}\":\n{}", s, lenses);
+        }
+    });
+    result.map(|_| lenses)
+}
+
+fn input() -> BufReader<File> {
+    BufReader::new(File::open("data/day15.txt").unwrap())
+}
+
 pub fn part1() -> usize {
-    HolidayAsciiStringHelper::load("data/day15.txt").calc_hash_sum()
+    steps(input()).map(|s| hash(&s) as usize).sum()
 }
 
 pub fn part2() -> usize {
-    let mut helper = HolidayAsciiStringHelper::load("data/day15.txt");
-    helper.init_lenses();
-    helper.focusing_power()
+    init_lenses(input()).focusing_power()
+}
+
+pub fn trace_part2<W: Write>(out: &mut W) -> io::Result<usize> {
+    Ok(trace_lenses(input(), out)?.focusing_power())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const SAMPLE: &str = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7\n";
+
+    #[test]
+    fn test_hashmap() {
+        assert_eq!(hash("HASH"), 52);
+        assert_eq!(
+            steps(SAMPLE.as_bytes())
+                .map(|s| hash(&s) as usize)
+                .sum::<usize>(),
+            1320
+        );
+        let lenses = init_lenses(SAMPLE.as_bytes());
+        assert_eq!(lenses.focusing_power(), 145);
+        assert_eq!(lenses.len(), 5);
+        assert_eq!(lenses.get("ot"), Some(&7));
+        assert_eq!(lenses.get("qp"), None);
+        let order: Vec<_> = lenses.iter().map(|(b, s, l, _)| (b, s, l)).collect();
+        assert_eq!(
+            order,
+            vec![
+                (0, 0, "rn"),
+                (0, 1, "cm"),
+                (3, 0, "ot"),
+                (3, 1, "ab"),
+                (3, 2, "pc")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut out = vec![];
+        let lenses = trace_lenses(SAMPLE.as_bytes(), &mut out).unwrap();
+        assert_eq!(lenses.focusing_power(), 145);
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.starts_with("After \"rn=1\":\nBox 0: [rn 1]\n\nAfter \"cm-\":\nBox 0: [rn 1]\n\n")
+        );
+        assert!(
+            out.ends_with("After \"ot=7\":\nBox 0: [rn 1] [cm 2]\nBox 3: [ot 7] [ab 5] [pc 6]\n\n")
+        );
+        assert!(out.contains("After \"qp-\":\nBox 0: [rn 1] [cm 2]\n\n"));
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 511257);
@@ -1,20 +1,19 @@
-use std::{collections::HashSet, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+    io::{self, Write},
+    thread,
+    time::Duration,
+};
 
-use crate::util::{Coord2D, Grid};
+use rayon::prelude::*;
 
-type Coord = Coord2D<usize>;
+use crate::util::{load, Coord2D};
 
-#[derive(Debug)]
-enum Object {
-    NONE,
-    SLASH,
-    BACKSLASH,
-    VERTICAL,
-    HORIZONTAL,
-}
+type Coord = Coord2D<usize>;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
-enum Direction {
+pub enum Direction {
     LEFT,
     RIGHT,
     TOP,
@@ -22,12 +21,22 @@ enum Direction {
 }
 
 impl Direction {
-    fn is_horizontal(&self) -> bool {
-        *self == Direction::LEFT || *self == Direction::RIGHT
+    fn bit(&self) -> u8 {
+        match self {
+            Direction::LEFT => 1,
+            Direction::RIGHT => 2,
+            Direction::TOP => 4,
+            Direction::BOTTOM => 8,
+        }
     }
 
-    fn is_vertical(&self) -> bool {
-        *self == Direction::TOP || *self == Direction::BOTTOM
+    fn opposite(&self) -> Direction {
+        match self {
+            Direction::LEFT => Direction::RIGHT,
+            Direction::RIGHT => Direction::LEFT,
+            Direction::TOP => Direction::BOTTOM,
+            Direction::BOTTOM => Direction::TOP,
+        }
     }
 
     fn reflect_slash(&self) -> Direction {
@@ -49,91 +58,194 @@ impl Direction {
     }
 }
 
-#[derive(Debug)]
-struct Beam {
-    pos: Coord,
-    dir: Direction,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Beam {
+    pub pos: Coord,
+    pub dir: Direction,
 }
 
 impl Beam {
-    fn new(x: usize, y: usize, dir: Direction) -> Self {
+    pub fn new(x: usize, y: usize, dir: Direction) -> Self {
         Beam {
             pos: Coord2D::new(x, y),
             dir,
         }
     }
 
-    fn reflect_slash(mut self) -> Self {
-        self.dir = self.dir.reflect_slash();
-        self
+    pub fn turn(self, dir: Direction) -> Self {
+        Beam { pos: self.pos, dir }
     }
+}
+
+// Anything that can sit on a square of the contraption.
+pub trait Element: Debug + Send + Sync {
+    // Beams leaving for a beam entering the square. The core moves each of
+    // them one square further, so they may leave from elsewhere (portals).
+    fn emit(&self, beam: Beam) -> Vec<Beam>;
+}
+
+// '.'
+#[derive(Debug)]
+struct Empty;
 
-    fn reflect_backslash(mut self) -> Self {
-        self.dir = self.dir.reflect_backslash();
-        self
+impl Element for Empty {
+    fn emit(&self, beam: Beam) -> Vec<Beam> {
+        vec![beam]
     }
 }
 
+// '/' and '\\'
 #[derive(Debug)]
-struct Square {
-    object: Object,
-    energized: HashSet<Direction>,
-}
-
-impl Square {
-    fn new(object: Object) -> Self {
-        Square {
-            object,
-            energized: HashSet::new(),
-        }
-    }
-
-    fn energize(&mut self, beam: Beam) -> Vec<Beam> {
-        if self.energized.contains(&beam.dir) {
-            vec![]
-        } else {
-            self.energized.insert(beam.dir);
-            match self.object {
-                Object::NONE => vec![beam],
-                Object::SLASH => vec![beam.reflect_slash()],
-                Object::BACKSLASH => vec![beam.reflect_backslash()],
-                Object::HORIZONTAL if beam.dir.is_horizontal() => vec![beam],
-                Object::HORIZONTAL => vec![
-                    Beam {
-                        pos: beam.pos,
-                        dir: Direction::LEFT,
-                    },
-                    Beam {
-                        pos: beam.pos,
-                        dir: Direction::RIGHT,
-                    },
-                ],
-                Object::VERTICAL if beam.dir.is_vertical() => vec![beam],
-                Object::VERTICAL => vec![
-                    Beam {
-                        pos: beam.pos,
-                        dir: Direction::TOP,
-                    },
-                    Beam {
-                        pos: beam.pos,
-                        dir: Direction::BOTTOM,
-                    },
-                ],
-            }
+struct Mirror {
+    slash: bool,
+}
+
+impl Element for Mirror {
+    fn emit(&self, beam: Beam) -> Vec<Beam> {
+        match self.slash {
+            true => vec![beam.turn(beam.dir.reflect_slash())],
+            false => vec![beam.turn(beam.dir.reflect_backslash())],
+        }
+    }
+}
+
+// '(' and ')': a '/' or '\\' mirror that only reflects beams travelling
+// right or left respectively, everything else passes through
+#[derive(Debug)]
+struct OneWayMirror {
+    mirror: Mirror,
+    reflects: Direction,
+}
+
+impl Element for OneWayMirror {
+    fn emit(&self, beam: Beam) -> Vec<Beam> {
+        match beam.dir == self.reflects {
+            true => self.mirror.emit(beam),
+            false => vec![beam],
+        }
+    }
+}
+
+// '|' and '-': beams along the splitter pass, others split to its ends
+#[derive(Debug)]
+struct Splitter {
+    ends: [Direction; 2],
+}
+
+impl Element for Splitter {
+    fn emit(&self, beam: Beam) -> Vec<Beam> {
+        match self.ends.contains(&beam.dir) {
+            true => vec![beam],
+            false => self.ends.iter().map(|d| beam.turn(*d)).collect(),
+        }
+    }
+}
+
+// '+': splits to straight on, left and right
+#[derive(Debug)]
+struct ThreeWaySplitter;
+
+impl Element for ThreeWaySplitter {
+    fn emit(&self, beam: Beam) -> Vec<Beam> {
+        [
+            Direction::LEFT,
+            Direction::RIGHT,
+            Direction::TOP,
+            Direction::BOTTOM,
+        ]
+        .into_iter()
+        .filter(|d| *d != beam.dir.opposite())
+        .map(|d| beam.turn(d))
+        .collect()
+    }
+}
+
+// '#'
+#[derive(Debug)]
+struct Absorber;
+
+impl Element for Absorber {
+    fn emit(&self, _: Beam) -> Vec<Beam> {
+        vec![]
+    }
+}
+
+// '0' to '9', in pairs: a beam entering one continues from the other
+#[derive(Debug)]
+struct Portal {
+    exit: Coord,
+}
+
+impl Element for Portal {
+    fn emit(&self, beam: Beam) -> Vec<Beam> {
+        vec![Beam {
+            pos: self.exit,
+            dir: beam.dir,
+        }]
+    }
+}
+
+fn builtin_element(c: char) -> Option<Box<dyn Element>> {
+    let element: Box<dyn Element> = match c {
+        '.' => Box::new(Empty),
+        '/' => Box::new(Mirror { slash: true }),
+        '\\' => Box::new(Mirror { slash: false }),
+        '(' => Box::new(OneWayMirror {
+            mirror: Mirror { slash: true },
+            reflects: Direction::RIGHT,
+        }),
+        ')' => Box::new(OneWayMirror {
+            mirror: Mirror { slash: false },
+            reflects: Direction::LEFT,
+        }),
+        '|' => Box::new(Splitter {
+            ends: [Direction::TOP, Direction::BOTTOM],
+        }),
+        '-' => Box::new(Splitter {
+            ends: [Direction::LEFT, Direction::RIGHT],
+        }),
+        '+' => Box::new(ThreeWaySplitter),
+        '#' => Box::new(Absorber),
+        _ => return None,
+    };
+    Some(element)
+}
+
+// The mirror layout, parsed once and shared by all beams.
+#[derive(Debug)]
+pub struct Contraption {
+    pub width: usize,
+    pub height: usize,
+    objects: Vec<Box<dyn Element>>,
+    // characters the elements were parsed from
+    layout: Vec<char>,
+}
+
+// Directions in which beams entered each square, one bit per direction.
+#[derive(Debug, Clone)]
+pub struct Energized {
+    width: usize,
+    squares: Vec<u8>,
+}
+
+impl Energized {
+    fn new(width: usize, height: usize) -> Self {
+        Energized {
+            width,
+            squares: vec![0; width * height],
         }
     }
 
-    fn is_energized(&self) -> bool {
-        !self.energized.is_empty()
+    pub fn energy(&self) -> usize {
+        self.squares.iter().filter(|s| **s != 0).count()
     }
 }
 
-impl Display for Grid<usize, Square> {
+impl Display for Energized {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let s = self.squares.get(&Coord2D::new(x, y)).unwrap();
-                write!(f, "{}", if s.is_energized() { '#' } else { '.' })?
+        for row in self.squares.chunks(self.width) {
+            for s in row {
+                write!(f, "{}", if *s != 0 { '#' } else { '.' })?
             }
             writeln!(f)?;
         }
@@ -141,79 +253,452 @@ impl Display for Grid<usize, Square> {
     }
 }
 
-impl Grid<usize, Square> {
-    fn load() -> Self {
-        Grid::from_file("data/day16.txt", |c, _| match c {
-            '.' => Some(Square::new(Object::NONE)),
-            '|' => Some(Square::new(Object::VERTICAL)),
-            '-' => Some(Square::new(Object::HORIZONTAL)),
-            '/' => Some(Square::new(Object::SLASH)),
-            '\\' => Some(Square::new(Object::BACKSLASH)),
-            _ => unreachable!("Invalid char '{}'", c),
-        })
-    }
-
-    fn move1(&self, Coord { x, y }: &Coord, dir: &Direction) -> Option<Coord> {
-        match dir {
-            Direction::BOTTOM if *y < self.height - 1 => Some(Coord2D::new(*x, *y + 1)),
-            Direction::TOP if *y > 0 => Some(Coord2D::new(*x, *y - 1)),
-            Direction::LEFT if *x > 0 => Some(Coord2D::new(*x - 1, *y)),
-            Direction::RIGHT if *x < self.width - 1 => Some(Coord2D::new(*x + 1, *y)),
-            _ => None,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    Serial,
+    Parallel,
+    // trace the paths between splitters once and combine those per beam
+    Memoised,
+}
+
+impl Contraption {
+    pub fn new<S: AsRef<str>>(lines: &[S]) -> Self {
+        Contraption::with_elements(lines, |_| None)
+    }
+
+    // custom elements take precedence over the built-in ones
+    pub fn with_elements<S, F>(lines: &[S], custom: F) -> Self
+    where
+        S: AsRef<str>,
+        F: Fn(char) -> Option<Box<dyn Element>>,
+    {
+        let chars: Vec<Vec<char>> = lines.iter().map(|l| l.as_ref().chars().collect()).collect();
+        let mut portals: HashMap<char, Vec<Coord>> = HashMap::new();
+        for (y, row) in chars.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                if c.is_ascii_digit() && custom(*c).is_none() {
+                    portals.entry(*c).or_default().push(Coord2D::new(x, y));
+                }
+            }
+        }
+        let objects = chars
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                let portals = &portals;
+                let custom = &custom;
+                row.iter().enumerate().map(move |(x, c)| {
+                    if let Some(element) = custom(*c).or_else(|| builtin_element(*c)) {
+                        return element;
+                    }
+                    match portals.get(c).map(|p| p.as_slice()) {
+                        Some([a, b]) => {
+                            let exit = if *a == Coord2D::new(x, y) { *b } else { *a };
+                            Box::new(Portal { exit }) as Box<dyn Element>
+                        }
+                        Some(_) => panic!("Portal '{}' needs exactly two ends", c),
+                        None => unreachable!("Invalid char '{}'", c),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        Contraption {
+            width: chars[0].len(),
+            height: chars.len(),
+            objects,
+            layout: chars.into_iter().flatten().collect(),
         }
     }
 
-    fn energize(&mut self, mut beams: Vec<Beam>) {
-        loop {
-            if beams.is_empty() {
-                break;
+    fn load() -> Self {
+        Contraption::new(&load::<String>("data/day16.txt"))
+    }
+
+    fn index(&self, Coord { x, y }: &Coord) -> usize {
+        y * self.width + x
+    }
+
+    fn move1(
+        &self,
+        Beam {
+            pos: Coord { x, y },
+            dir,
+        }: Beam,
+    ) -> Option<Beam> {
+        let pos = match dir {
+            Direction::BOTTOM if y < self.height - 1 => Coord2D::new(x, y + 1),
+            Direction::TOP if y > 0 => Coord2D::new(x, y - 1),
+            Direction::LEFT if x > 0 => Coord2D::new(x - 1, y),
+            Direction::RIGHT if x < self.width - 1 => Coord2D::new(x + 1, y),
+            _ => return None,
+        };
+        Some(Beam { pos, dir })
+    }
+
+    // beams entering the next squares
+    fn propagate(&self, beam: Beam) -> impl Iterator<Item = Beam> + '_ {
+        self.objects[self.index(&beam.pos)]
+            .emit(beam)
+            .into_iter()
+            .filter_map(|b| self.move1(b))
+    }
+
+    pub fn energize(&self, start: Beam) -> Energized {
+        let mut energized = Energized::new(self.width, self.height);
+        let mut beams = vec![start];
+        while let Some(beam) = beams.pop() {
+            let square = &mut energized.squares[self.index(&beam.pos)];
+            if *square & beam.dir.bit() == 0 {
+                *square |= beam.dir.bit();
+                beams.extend(self.propagate(beam));
             }
-            let beam = beams.pop().unwrap();
-            let new_beams = self.squares.get_mut(&beam.pos).unwrap().energize(beam);
-            new_beams
+        }
+        energized
+    }
+
+    // energized squares after every round of moving all beams one square
+    pub fn frames(&self, start: Beam) -> Vec<Energized> {
+        let mut energized = Energized::new(self.width, self.height);
+        let mut frames = vec![];
+        let mut beams = vec![start];
+        while !beams.is_empty() {
+            beams = beams
                 .into_iter()
-                .filter_map(|b| {
-                    self.move1(&b.pos, &b.dir)
-                        .map(|p| Beam { pos: p, dir: b.dir })
+                .filter(|b| {
+                    let square = &mut energized.squares[self.index(&b.pos)];
+                    let new = *square & b.dir.bit() == 0;
+                    *square |= b.dir.bit();
+                    new
                 })
-                .for_each(|b| beams.push(b));
+                .flat_map(|b| self.propagate(b).collect::<Vec<_>>())
+                .collect();
+            frames.push(energized.clone());
         }
+        frames
     }
 
-    fn energy(&self) -> usize {
-        self.squares.values().filter(|s| s.is_energized()).count()
+    // Layout with the beams on empty squares: an arrow for a single direction,
+    // the number of directions otherwise.
+    pub fn render(&self, energized: &Energized) -> String {
+        let mut out = String::new();
+        for (i, (c, s)) in self.layout.iter().zip(energized.squares.iter()).enumerate() {
+            out.push(match (*c, s.count_ones()) {
+                ('.', 0) => '.',
+                ('.', 1) => match *s {
+                    1 => '<',
+                    2 => '>',
+                    4 => '^',
+                    _ => 'v',
+                },
+                ('.', n) => char::from_digit(n, 10).unwrap(),
+                (c, _) => c,
+            });
+            if (i + 1) % self.width == 0 {
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    pub fn animate<W: Write>(&self, start: Beam, out: &mut W, delay: Duration) -> io::Result<()> {
+        for frame in self.frames(start) {
+            // clear screen, cursor to top left
+            write!(out, "\x1b[2J\x1b[H{}", self.render(&frame))?;
+            out.flush()?;
+            thread::sleep(delay);
+        }
+        Ok(())
+    }
+
+    // every beam entering from an edge, pointing inwards
+    pub fn edge_beams(&self) -> Vec<Beam> {
+        let (w, h) = (self.width, self.height);
+        (0..w)
+            .flat_map(|x| {
+                [
+                    Beam::new(x, 0, Direction::BOTTOM),
+                    Beam::new(x, h - 1, Direction::TOP),
+                ]
+            })
+            .chain((0..h).flat_map(|y| {
+                [
+                    Beam::new(0, y, Direction::RIGHT),
+                    Beam::new(w - 1, y, Direction::LEFT),
+                ]
+            }))
+            .collect()
+    }
+
+    // energy for each of the given starting beams
+    pub fn evaluate(&self, beams: &[Beam], strategy: Strategy) -> Vec<(Beam, usize)> {
+        match strategy {
+            Strategy::Serial => beams
+                .iter()
+                .map(|b| (*b, self.energize(*b).energy()))
+                .collect(),
+            Strategy::Parallel => beams
+                .par_iter()
+                .map(|b| (*b, self.energize(*b).energy()))
+                .collect(),
+            Strategy::Memoised => {
+                let mut segments = Segments::new(self);
+                beams.iter().map(|b| (*b, segments.energy(*b))).collect()
+            }
+        }
+    }
+
+    // first of the edge beams with the most energy
+    pub fn best_entry(&self, strategy: Strategy) -> Option<(Beam, usize)> {
+        self.evaluate(&self.edge_beams(), strategy)
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, energy)| *energy)
+    }
+}
+
+// Squares energized by a beam, as words of bits
+type Squares = Vec<u64>;
+
+fn union(acc: &mut Squares, other: &Squares) {
+    acc.iter_mut().zip(other.iter()).for_each(|(a, b)| *a |= b);
+}
+
+// A splitter hit from the side (a beam entering it) starts two new paths.
+// Everything up to the next splitter is traced once and cached, as is
+// everything reachable from each splitter.
+struct Segments<'a> {
+    contraption: &'a Contraption,
+    // squares up to, and splitters at the end of, the path of a beam
+    paths: HashMap<Beam, (Squares, Vec<Beam>)>,
+    reachable: HashMap<Beam, Squares>,
+}
+
+impl<'a> Segments<'a> {
+    fn new(contraption: &'a Contraption) -> Self {
+        Segments {
+            contraption,
+            paths: HashMap::new(),
+            reachable: HashMap::new(),
+        }
+    }
+
+    fn no_squares(&self) -> Squares {
+        vec![0; self.contraption.objects.len().div_ceil(64)]
+    }
+
+    // follow a beam until it splits, leaves or loops
+    fn path(&mut self, start: Beam) -> &(Squares, Vec<Beam>) {
+        if !self.paths.contains_key(&start) {
+            let c = self.contraption;
+            let mut squares = self.no_squares();
+            let mut splits = vec![];
+            let mut seen = HashSet::new();
+            let mut beam = Some(start);
+            while let Some(b) = beam {
+                if !seen.insert(b) {
+                    break;
+                }
+                let i = c.index(&b.pos);
+                squares[i / 64] |= 1 << (i % 64);
+                let next: Vec<Beam> = c.propagate(b).collect();
+                beam = match next.len() {
+                    1 => Some(next[0]),
+                    _ => {
+                        splits.extend(next);
+                        None
+                    }
+                };
+            }
+            self.paths.insert(start, (squares, splits));
+        }
+        self.paths.get(&start).unwrap()
+    }
+
+    fn energy(&mut self, start: Beam) -> usize {
+        if !self.reachable.contains_key(&start) {
+            let mut squares = self.no_squares();
+            let mut seen = HashSet::from([start]);
+            let mut todo = vec![start];
+            while let Some(b) = todo.pop() {
+                if let Some(r) = self.reachable.get(&b) {
+                    union(&mut squares, r);
+                    continue;
+                }
+                let (path, splits) = self.path(b).clone();
+                union(&mut squares, &path);
+                todo.extend(splits.into_iter().filter(|s| seen.insert(*s)));
+            }
+            self.reachable.insert(start, squares);
+        }
+        self.reachable
+            .get(&start)
+            .unwrap()
+            .iter()
+            .map(|w| w.count_ones() as usize)
+            .sum()
     }
 }
 
 pub fn part1() -> usize {
-    let mut g = Grid::load();
-    g.energize(vec![Beam::new(0, 0, Direction::RIGHT)]);
-    g.energy()
+    Contraption::load()
+        .energize(Beam::new(0, 0, Direction::RIGHT))
+        .energy()
 }
 
 pub fn part2() -> usize {
-    let mut beams = vec![];
-    for i in 0..110 {
-        beams.push(Beam::new(i, 0, Direction::BOTTOM));
-        beams.push(Beam::new(i, 109, Direction::TOP));
-        beams.push(Beam::new(0, i, Direction::RIGHT));
-        beams.push(Beam::new(109, i, Direction::LEFT));
-    }
-    beams
-        .into_iter()
-        .map(|b| {
-            let mut g = Grid::load();
-            g.energize(vec![b]);
-            g.energy()
-        })
-        .max()
+    Contraption::load()
+        .best_entry(Strategy::Parallel)
         .unwrap()
+        .1
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Instant;
+
     use super::*;
 
+    const SAMPLE: [&str; 10] = [
+        ".|...\\....",
+        "|.-.\\.....",
+        ".....|-...",
+        "........|.",
+        "..........",
+        ".........\\",
+        "..../.\\\\..",
+        ".-.-/..|..",
+        ".|....-|.\\",
+        "..//.|....",
+    ];
+
+    #[test]
+    fn test_strategies() {
+        let c = Contraption::new(&SAMPLE);
+        assert_eq!(c.energize(Beam::new(0, 0, Direction::RIGHT)).energy(), 46);
+        let beams = c.edge_beams();
+        assert_eq!(beams.len(), 40);
+        for strategy in [Strategy::Serial, Strategy::Parallel, Strategy::Memoised] {
+            assert_eq!(
+                c.best_entry(strategy),
+                Some((Beam::new(3, 0, Direction::BOTTOM), 51))
+            );
+        }
+        let mut segments = Segments::new(&c);
+        for b in beams {
+            assert_eq!(segments.energy(b), c.energize(b).energy());
+        }
+    }
+
+    // cargo test --release bench_part2 -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_part2() {
+        let c = Contraption::load();
+        for strategy in [Strategy::Serial, Strategy::Parallel, Strategy::Memoised] {
+            let start = Instant::now();
+            let best = c.best_entry(strategy);
+            println!("{:?}: {:?} in {:?}", strategy, best, start.elapsed());
+        }
+    }
+
+    #[test]
+    fn test_non_square_grid() {
+        let c = Contraption::new(&["..\\", "...", "-./", "...", "..|"]);
+        assert_eq!(c.edge_beams().len(), 2 * 3 + 2 * 5);
+        let evaluated = c.evaluate(
+            &[
+                Beam::new(0, 0, Direction::RIGHT),
+                Beam::new(1, 4, Direction::TOP),
+            ],
+            Strategy::Serial,
+        );
+        assert_eq!(evaluated[0].1, 7);
+        assert_eq!(evaluated[1].1, 5);
+    }
+
+    #[test]
+    fn test_elements() {
+        // absorber stops the beam
+        let c = Contraption::new(&["..#.."]);
+        assert_eq!(c.energize(Beam::new(0, 0, Direction::RIGHT)).energy(), 3);
+        // three-way splitter
+        let c = Contraption::new(&["...", ".+.", "..."]);
+        assert_eq!(c.energize(Beam::new(0, 1, Direction::RIGHT)).energy(), 5);
+        // one-way mirror reflects going right, passes going left
+        let c = Contraption::new(&["...", ".(.", "..."]);
+        assert_eq!(
+            c.energize(Beam::new(0, 1, Direction::RIGHT)).to_string(),
+            ".#.\n##.\n...\n"
+        );
+        assert_eq!(
+            c.energize(Beam::new(2, 1, Direction::LEFT)).to_string(),
+            "...\n###\n...\n"
+        );
+        // portals
+        let c = Contraption::new(&["1....", ".....", "...1."]);
+        assert_eq!(
+            c.energize(Beam::new(0, 0, Direction::RIGHT)).to_string(),
+            "#....\n.....\n....#\n"
+        );
+        // custom element: a mirror that sends everything back
+        #[derive(Debug)]
+        struct Reverser;
+        impl Element for Reverser {
+            fn emit(&self, beam: Beam) -> Vec<Beam> {
+                vec![beam.turn(beam.dir.opposite())]
+            }
+        }
+        let c = Contraption::with_elements(&["...R."], |c| match c {
+            'R' => Some(Box::new(Reverser)),
+            _ => None,
+        });
+        let e = c.energize(Beam::new(0, 0, Direction::RIGHT));
+        assert_eq!(e.to_string(), "####.\n");
+        let mut segments = Segments::new(&c);
+        assert_eq!(segments.energy(Beam::new(0, 0, Direction::RIGHT)), 4);
+    }
+
+    #[test]
+    fn test_render() {
+        let c = Contraption::new(&SAMPLE);
+        let start = Beam::new(0, 0, Direction::RIGHT);
+        let frames = c.frames(start);
+        assert_eq!(frames.last().unwrap().energy(), 46);
+        assert_eq!(frames[2].energy(), 3);
+        assert_eq!(
+            c.render(&frames[1]),
+            ">|...\\....\n\
+             |.-.\\.....\n\
+             .....|-...\n\
+             ........|.\n\
+             ..........\n\
+             .........\\\n\
+             ..../.\\\\..\n\
+             .-.-/..|..\n\
+             .|....-|.\\\n\
+             ..//.|....\n"
+        );
+        assert_eq!(
+            c.render(&c.energize(start)),
+            ">|<<<\\....\n\
+             |v-.\\^....\n\
+             .v...|->>>\n\
+             .v...v^.|.\n\
+             .v...v^...\n\
+             .v...v^..\\\n\
+             .v../2\\\\..\n\
+             <->-/vv|..\n\
+             .|<<<2-|.\\\n\
+             .v//.|.v..\n"
+        );
+        let mut out = vec![];
+        c.animate(start, &mut out, Duration::ZERO).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap().matches("\x1b[2J").count(),
+            frames.len()
+        );
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 8323);
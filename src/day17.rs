@@ -1,22 +1,60 @@
 use std::{
-    cmp::Ordering,
-    collections::{BinaryHeap, HashSet},
     fmt::Debug,
+    fs::File,
+    io::{self, BufWriter, Write},
 };
 
-use crate::util::{char2num, load, Coord2D};
+use crate::util::{char2num, load, shortest_path, Coord2D};
 
 type Coord = Coord2D<i32>;
 type Map = Vec<Vec<usize>>;
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum Direction {
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Direction {
     NORTH,
     EAST,
     SOUTH,
     WEST,
 }
 
+impl Direction {
+    fn delta(&self) -> (i32, i32) {
+        match self {
+            Direction::NORTH => (0, -1),
+            Direction::EAST => (1, 0),
+            Direction::SOUTH => (0, 1),
+            Direction::WEST => (-1, 0),
+        }
+    }
+
+    fn arrow(&self) -> char {
+        match self {
+            Direction::NORTH => '^',
+            Direction::EAST => '>',
+            Direction::SOUTH => 'v',
+            Direction::WEST => '<',
+        }
+    }
+
+    fn turn_to(&self, other: &Direction) -> Turn {
+        let idx = |d: &Direction| DIRS.iter().position(|o| o == d).unwrap();
+        match (idx(other) + 4 - idx(self)) % 4 {
+            0 => Turn::Straight,
+            1 => Turn::Right,
+            2 => Turn::Back,
+            _ => Turn::Left,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Turn {
+    Straight,
+    Left,
+    Right,
+    Back,
+}
+
 const DIRS: &[Direction; 4] = &[
     Direction::NORTH,
     Direction::EAST,
@@ -24,9 +62,10 @@ const DIRS: &[Direction; 4] = &[
     Direction::WEST,
 ];
 
-#[derive(Debug, Eq, Clone, Copy)]
+// heat loss is tracked by the search, so states that differ only in how
+// they were reached are the same state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct State {
-    cost: usize,
     pos: Coord,
     dir: Direction,
     cnt_straight: usize,
@@ -35,14 +74,14 @@ struct State {
 impl State {
     fn new(x: i32, y: i32, dir: Direction) -> Self {
         State {
-            cost: 0,
             pos: Coord2D::new(x, y),
             dir,
             cnt_straight: 0,
         }
     }
 
-    fn step(&self, map: &Map, nd: &Direction, dx: i32, dy: i32) -> State {
+    // the next state and the heat lost entering its square
+    fn step(&self, map: &Map, nd: &Direction, dx: i32, dy: i32) -> (State, usize) {
         fn new_cnt(d1: &Direction, d2: &Direction, cnt: usize) -> usize {
             if d1 == d2 {
                 cnt + 1
@@ -51,49 +90,12 @@ impl State {
             }
         }
         let (nx, ny) = (self.pos.x + dx, self.pos.y + dy);
-        State {
-            cost: self.cost + map[ny as usize][nx as usize],
+        let next = State {
             pos: Coord2D::new(nx, ny),
             dir: *nd,
-            cnt_straight: new_cnt(&self.dir, &nd, self.cnt_straight),
-        }
-    }
-
-    fn key(&self) -> i32 {
-        let d = match self.dir {
-            Direction::NORTH => 1,
-            Direction::EAST => 2,
-            Direction::SOUTH => 3,
-            Direction::WEST => 4,
+            cnt_straight: new_cnt(&self.dir, nd, self.cnt_straight),
         };
-        (self.cost as i32) << 22
-            | (self.cnt_straight as i32) << 18
-            | d << 16
-            | (self.pos.x << 8)
-            | self.pos.y
-    }
-}
-
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
-    }
-}
-
-impl PartialEq for State {
-    fn eq(&self, other: &Self) -> bool {
-        Some(Ordering::Equal) == self.partial_cmp(other)
-    }
-}
-
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self.cost.partial_cmp(&other.cost) {
-            Some(Ordering::Equal) => (self.pos.x + self.pos.y)
-                .partial_cmp(&(other.pos.x + other.pos.y))
-                .map(|o| o.reverse()),
-            ord => ord.map(|o| o.reverse()),
-        }
+        (next, map[ny as usize][nx as usize])
     }
 }
 
@@ -105,171 +107,380 @@ fn input(file: &str) -> Map {
 }
 
 trait Move {
-    fn step(&self, s: &State, d: &Direction) -> Option<State>;
+    // the next state and the heat lost getting there
+    fn step(&self, s: &State, d: &Direction) -> Option<(State, usize)>;
+    // lower bound on the heat lost entering any square
+    fn min_square_cost(&self) -> usize;
 }
 
-struct Crucible {
+// Moves in straight runs of min_straight to max_straight squares, changing
+// direction only as allowed by turns. Penalties are added to the heat loss.
+pub struct Mover {
     map: Map,
+    min_straight: usize,
+    max_straight: usize,
+    turns: Vec<Turn>,
+    turn_penalty: usize,
+    move_penalty: usize,
 }
 
-impl Move for Crucible {
-    // need to move maximum 3 in same dir, no U-turn
-    fn step(&self, s: &State, d: &Direction) -> Option<State> {
-        match d {
-            Direction::NORTH
-                if s.pos.y > 0
-                    && s.dir != Direction::SOUTH
-                    && (s.dir != Direction::NORTH || s.cnt_straight < 3) =>
-            {
-                Some(s.step(&self.map, &d, 0, -1))
-            }
-            Direction::EAST
-                if s.pos.x < self.map[0].len() as i32 - 1
-                    && s.dir != Direction::WEST
-                    && (s.dir != Direction::EAST || s.cnt_straight < 3) =>
-            {
-                Some(s.step(&self.map, &d, 1, 0))
-            }
-            Direction::SOUTH
-                if s.pos.y < self.map.len() as i32 - 1
-                    && s.dir != Direction::NORTH
-                    && (s.dir != Direction::SOUTH || s.cnt_straight < 3) =>
-            {
-                Some(s.step(&self.map, &d, 0, 1))
-            }
-            Direction::WEST
-                if s.pos.x > 0
-                    && s.dir != Direction::EAST
-                    && (s.dir != Direction::WEST || s.cnt_straight < 3) =>
-            {
-                Some(s.step(&self.map, &d, -1, 0))
-            }
-            _ => None,
+impl Mover {
+    pub fn new(map: Map, min_straight: usize, max_straight: usize) -> Self {
+        Mover {
+            map,
+            min_straight,
+            max_straight,
+            turns: vec![Turn::Left, Turn::Right],
+            turn_penalty: 0,
+            move_penalty: 0,
         }
     }
-}
 
-struct UltraCrucible {
-    map: Map,
+    pub fn crucible(map: Map) -> Self {
+        Mover::new(map, 1, 3)
+    }
+
+    pub fn ultra_crucible(map: Map) -> Self {
+        Mover::new(map, 4, 10)
+    }
+
+    pub fn with_turns(mut self, turns: &[Turn]) -> Self {
+        self.turns = turns.to_vec();
+        self
+    }
+
+    pub fn with_penalties(mut self, turn_penalty: usize, move_penalty: usize) -> Self {
+        self.turn_penalty = turn_penalty;
+        self.move_penalty = move_penalty;
+        self
+    }
+
+    fn on_map(&self, s: &State, d: &Direction, squares: usize) -> bool {
+        let (dx, dy) = d.delta();
+        let (x, y) = (s.pos.x + dx * squares as i32, s.pos.y + dy * squares as i32);
+        x >= 0 && y >= 0 && y < self.map.len() as i32 && x < self.map[0].len() as i32
+    }
 }
 
-impl Move for UltraCrucible {
-    // need to move minimum 4 and maximum 10 in same dir, no U-turn
-    fn step(&self, s: &State, d: &Direction) -> Option<State> {
-        fn steps(d1: &Direction, d2: &Direction) -> usize {
-            if d1 != d2 {
-                4
-            } else {
-                1
-            }
+impl Move for Mover {
+    fn step(&self, s: &State, d: &Direction) -> Option<(State, usize)> {
+        // the first move from the start is always a new run
+        let turn = match s.cnt_straight {
+            0 => None,
+            _ => Some(s.dir.turn_to(d)),
+        };
+        let squares = match turn {
+            Some(Turn::Straight) if s.cnt_straight < self.max_straight => 1,
+            Some(Turn::Straight) => return None,
+            Some(t) if !self.turns.contains(&t) => return None,
+            _ => self.min_straight,
+        };
+        if !self.on_map(s, d, squares) {
+            return None;
         }
-        let width = self.map[0].len() as i32;
-        let height = self.map.len() as i32;
-        match d {
-            Direction::NORTH
-                if s.pos.y > 0
-                    && s.dir != Direction::SOUTH
-                    && ((s.dir != Direction::NORTH && s.pos.y >= 4)
-                        || (s.dir == Direction::NORTH && s.cnt_straight < 10)) =>
-            {
-                Some(
-                    (0..steps(&s.dir, &Direction::NORTH))
-                        .fold(*s, |acc, _| acc.step(&self.map, &d, 0, -1)),
-                )
-            }
-            Direction::EAST
-                if s.pos.x < width - 1
-                    && s.dir != Direction::WEST
-                    && ((s.dir != Direction::EAST && s.pos.x < width - 4)
-                        || (s.dir == Direction::EAST && s.cnt_straight < 10)) =>
-            {
-                Some(
-                    (0..steps(&s.dir, &Direction::EAST))
-                        .fold(*s, |acc, _| acc.step(&self.map, &d, 1, 0)),
-                )
-            }
-            Direction::SOUTH
-                if s.pos.y < height - 1
-                    && s.dir != Direction::NORTH
-                    && ((s.dir != Direction::SOUTH && s.pos.y < height - 4)
-                        || (s.dir == Direction::SOUTH && s.cnt_straight < 10)) =>
-            {
-                Some(
-                    (0..steps(&s.dir, &Direction::SOUTH))
-                        .fold(*s, |acc, _| acc.step(&self.map, &d, 0, 1)),
-                )
-            }
-            Direction::WEST
-                if s.pos.x > 0
-                    && s.dir != Direction::EAST
-                    && ((s.dir != Direction::WEST && s.pos.x >= 4)
-                        || (s.dir == Direction::WEST && s.cnt_straight < 10)) =>
-            {
-                Some(
-                    (0..steps(&s.dir, &Direction::WEST))
-                        .fold(*s, |acc, _| acc.step(&self.map, &d, -1, 0)),
-                )
-            }
-            _ => None,
+        let (dx, dy) = d.delta();
+        let (mut next, mut cost) = (*s, 0);
+        for _ in 0..squares {
+            let (n, c) = next.step(&self.map, d, dx, dy);
+            next = n;
+            cost += c + self.move_penalty;
         }
+        // a new run starts counting afresh, even in the same direction
+        next.cnt_straight = if turn == Some(Turn::Straight) {
+            s.cnt_straight + 1
+        } else {
+            squares
+        };
+        if matches!(turn, Some(t) if t != Turn::Straight) {
+            cost += self.turn_penalty;
+        }
+        Some((next, cost))
+    }
+
+    fn min_square_cost(&self) -> usize {
+        self.map.iter().flatten().min().unwrap_or(&0) + self.move_penalty
     }
 }
 
-fn travel(movable: impl Move, dst: Coord, mut q: BinaryHeap<State>) -> usize {
-    let mut visited = HashSet::new();
-    // let mut it = 0;
-    loop {
-        match q.pop() {
-            None => unreachable!("No path found"),
-            Some(state) => {
-                // it += 1;
-                // if it % 500000 == 0 {
-                //     println!(
-                //         "{:10} At ({}, {}) with cost {} (#Q = {})",
-                //         it,
-                //         state.pos.x,
-                //         state.pos.y,
-                //         state.cost,
-                //         q.len()
-                //     );
-                // }
-                if state.pos == dst {
-                    break state.cost;
-                }
-                DIRS.iter()
-                    .filter_map(|d| movable.step(&state, d))
-                    .for_each(|s| {
-                        let k = s.key();
-                        if !visited.contains(&k) {
-                            visited.insert(k);
-                            q.push(s)
-                        }
-                    });
-            }
+// squares entered, with the direction they were entered in
+pub type Route = Vec<(Coord, Direction)>;
+
+fn travel(movable: impl Move, start: State, dst: Coord) -> Option<(usize, Route)> {
+    // every remaining square costs at least this, which keeps the heuristic admissible
+    let min_cost = movable.min_square_cost();
+    let (cost, states) = shortest_path(
+        [start],
+        |s| {
+            DIRS.iter()
+                .filter_map(|d| movable.step(s, d))
+                .collect::<Vec<_>>()
+        },
+        |s| (s.pos.x.abs_diff(dst.x) + s.pos.y.abs_diff(dst.y)) as usize * min_cost,
+        |s| s.pos == dst,
+    )?;
+    // a single move may cover several squares
+    let mut route = vec![];
+    for (from, to) in states.iter().zip(states.iter().skip(1)) {
+        let mut pos = from.pos;
+        while pos != to.pos {
+            pos.x += (to.pos.x - pos.x).signum();
+            pos.y += (to.pos.y - pos.y).signum();
+            route.push((pos, to.dir));
         }
     }
+    Some((cost, route))
 }
 
-pub fn part1() -> usize {
-    let map = input("data/day17.txt");
-    let mut q = BinaryHeap::new();
-    q.push(State::new(0, 0, Direction::EAST));
+fn min_heat_loss<M: Move>(file: &str, movable: impl Fn(Map) -> M) -> Option<(usize, Route)> {
+    let map = input(file);
     let dst = Coord2D::new(map[0].len() as i32 - 1, map.len() as i32 - 1);
-    travel(Crucible { map }, dst, q)
+    travel(movable(map), State::new(0, 0, Direction::EAST), dst)
 }
 
-pub fn part2() -> usize {
+// heat map with the route drawn over it, as in the puzzle description
+pub fn render(map: &Map, route: &Route) -> String {
+    let mut grid: Vec<Vec<char>> = map
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&h| char::from_digit(h as u32, 10).unwrap())
+                .collect()
+        })
+        .collect();
+    for (c, d) in route {
+        grid[c.y as usize][c.x as usize] = d.arrow();
+    }
+    grid.into_iter()
+        .map(|row| row.into_iter().chain(['\n']).collect::<String>())
+        .collect()
+}
+
+// heat lost from the map after each step, without any mover penalties
+pub fn cumulative_heat_loss(map: &Map, route: &Route) -> Vec<usize> {
+    route
+        .iter()
+        .scan(0, |total, (c, _)| {
+            *total += map[c.y as usize][c.x as usize];
+            Some(*total)
+        })
+        .collect()
+}
+
+pub fn write_steps<W: Write>(out: &mut W, map: &Map, route: &Route) -> io::Result<()> {
+    for (i, ((c, d), total)) in route
+        .iter()
+        .zip(cumulative_heat_loss(map, route))
+        .enumerate()
+    {
+        writeln!(
+            out,
+            "{:>4}: ({}, {}) {} {}",
+            i + 1,
+            c.x,
+            c.y,
+            d.arrow(),
+            total
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_csv<W: Write>(out: &mut W, map: &Map, route: &Route) -> io::Result<()> {
+    writeln!(out, "step,x,y,direction,heat_loss")?;
+    for (i, ((c, d), total)) in route
+        .iter()
+        .zip(cumulative_heat_loss(map, route))
+        .enumerate()
+    {
+        writeln!(out, "{},{},{},{},{}", i + 1, c.x, c.y, d.arrow(), total)?;
+    }
+    Ok(())
+}
+
+// prints the part 1 route and its heat loss, and exports it to a CSV file
+pub fn show_route(filename: &str) -> io::Result<()> {
     let map = input("data/day17.txt");
-    let mut q = BinaryHeap::new();
-    q.push(State::new(0, 0, Direction::EAST));
     let dst = Coord2D::new(map[0].len() as i32 - 1, map.len() as i32 - 1);
-    travel(UltraCrucible { map }, dst, q)
+    let start = State::new(0, 0, Direction::EAST);
+    let (_, route) = travel(Mover::crucible(map.clone()), start, dst).unwrap();
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "{}", render(&map, &route))?;
+    write_steps(&mut stdout, &map, &route)?;
+    let mut out = BufWriter::new(File::create(filename)?);
+    write_csv(&mut out, &map, &route)?;
+    out.flush()
+}
+
+pub fn part1() -> usize {
+    min_heat_loss("data/day17.txt", Mover::crucible).unwrap().0
+}
+
+pub fn part2() -> usize {
+    min_heat_loss("data/day17.txt", Mover::ultra_crucible)
+        .unwrap()
+        .0
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn sample() -> Map {
+        [
+            "2413432311323",
+            "3215453535623",
+            "3255245654254",
+            "3446585845452",
+            "4546657867536",
+            "1438598798454",
+            "4457876987766",
+            "3637877979653",
+            "4654967986887",
+            "4564679986453",
+            "1224686865563",
+            "2546548887735",
+            "4322674655533",
+        ]
+        .iter()
+        .map(|l| l.chars().map(|c| char2num(c) as usize).collect())
+        .collect()
+    }
+
+    #[test]
+    fn test_route() {
+        let dst = Coord2D::new(12, 12);
+        let start = State::new(0, 0, Direction::EAST);
+        let (cost, route) = travel(Mover::crucible(sample()), start, dst).unwrap();
+        assert_eq!(cost, 102);
+        assert_eq!(route.last().unwrap().0, dst);
+        let map = sample();
+        let total: usize = route
+            .iter()
+            .map(|(c, _)| map[c.y as usize][c.x as usize])
+            .sum();
+        assert_eq!(total, cost);
+        let (cost, route) = travel(Mover::ultra_crucible(sample()), start, dst).unwrap();
+        assert_eq!(cost, 94);
+        assert_eq!(route.len(), 24);
+        // nowhere to go on a single square
+        let lonely = travel(Mover::crucible(vec![vec![1]]), start, Coord2D::new(1, 1));
+        assert_eq!(lonely, None);
+    }
+
+    #[test]
+    fn test_movers() {
+        let start = State::new(0, 0, Direction::EAST);
+        let map: Map = [
+            "111111111111",
+            "999999999991",
+            "999999999991",
+            "999999999991",
+            "999999999991",
+        ]
+        .iter()
+        .map(|l| l.chars().map(|c| char2num(c) as usize).collect())
+        .collect();
+        let dst = Coord2D::new(11, 4);
+        let ultra = travel(Mover::ultra_crucible(map.clone()), start, dst);
+        assert_eq!(ultra.unwrap().0, 71);
+        // turning one way only
+        let map: Map = vec![
+            vec![1, 1, 1, 1],
+            vec![9, 9, 9, 1],
+            vec![9, 9, 9, 1],
+            vec![9, 9, 9, 1],
+        ];
+        let dst = Coord2D::new(3, 3);
+        let righty = Mover::crucible(map.clone()).with_turns(&[Turn::Right]);
+        assert_eq!(travel(righty, start, dst).unwrap().0, 6);
+        let lefty = Mover::crucible(map).with_turns(&[Turn::Left]);
+        let (cost, route) = travel(lefty, start, dst).unwrap();
+        assert!(cost > 6);
+        assert!(route
+            .windows(2)
+            .all(|w| matches!(w[0].1.turn_to(&w[1].1), Turn::Straight | Turn::Left)));
+        // penalties
+        let line = vec![vec![1, 1, 1, 1]];
+        let mover = Mover::crucible(line).with_penalties(100, 2);
+        assert_eq!(travel(mover, start, Coord2D::new(3, 0)).unwrap().0, 3 * 3);
+        let square = vec![vec![1, 1], vec![1, 1]];
+        let mover = Mover::crucible(square).with_penalties(100, 0);
+        assert_eq!(travel(mover, start, Coord2D::new(1, 1)).unwrap().0, 102);
+    }
+
+    #[test]
+    fn test_render() {
+        let map = sample();
+        let (_, route) = travel(
+            Mover::crucible(sample()),
+            State::new(0, 0, Direction::EAST),
+            Coord2D::new(12, 12),
+        )
+        .unwrap();
+        let lines: Vec<String> = render(&map, &route).lines().map(String::from).collect();
+        assert_eq!(lines.len(), 13);
+        assert!(lines.iter().all(|l| l.len() == 13));
+        // every route square shows an arrow, the start keeps its digit
+        let arrows = lines
+            .iter()
+            .flat_map(|l| l.chars())
+            .filter(|c| "^>v<".contains(*c))
+            .count();
+        assert_eq!(arrows, route.len());
+        assert!(lines[0].starts_with('2'));
+        assert!(lines[12].ends_with('v') || lines[12].ends_with('>'));
+        let totals = cumulative_heat_loss(&map, &route);
+        assert_eq!(totals.len(), route.len());
+        assert_eq!(totals.last(), Some(&102));
+        let mut csv = vec![];
+        write_csv(&mut csv, &map, &route).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], "step,x,y,direction,heat_loss");
+        assert_eq!(rows.len(), route.len() + 1);
+        assert!(rows
+            .last()
+            .unwrap()
+            .starts_with(&format!("{},12,12,", route.len())));
+        assert!(rows.last().unwrap().ends_with(",102"));
+    }
+
+    #[test]
+    fn test_render_small() {
+        let map = vec![vec![1, 2], vec![3, 4]];
+        let route = vec![
+            (Coord2D::new(1, 0), Direction::EAST),
+            (Coord2D::new(1, 1), Direction::SOUTH),
+        ];
+        assert_eq!(render(&map, &route), "1>\n3v\n");
+        assert_eq!(cumulative_heat_loss(&map, &route), vec![2, 6]);
+        let mut steps = vec![];
+        write_steps(&mut steps, &map, &route).unwrap();
+        assert_eq!(
+            String::from_utf8(steps).unwrap(),
+            "   1: (1, 0) > 2\n   2: (1, 1) v 6\n"
+        );
+    }
+
+    #[test]
+    fn test_free_squares() {
+        // squares without heat loss must not make the search overshoot
+        let map = vec![vec![0, 0, 0], vec![0, 9, 1], vec![0, 0, 0]];
+        let start = State::new(0, 0, Direction::EAST);
+        let (cost, route) = travel(Mover::crucible(map), start, Coord2D::new(2, 2)).unwrap();
+        assert_eq!(cost, 0);
+        assert_eq!(route[0], (Coord2D::new(0, 1), Direction::SOUTH));
+    }
+
+    #[test]
+    fn test_expensive_map() {
+        // heat loss well beyond what fits alongside the position in a packed key
+        let map = vec![vec![9; 100]; 100];
+        let start = State::new(0, 0, Direction::EAST);
+        let (cost, _) = travel(Mover::crucible(map), start, Coord2D::new(99, 99)).unwrap();
+        assert_eq!(cost, 9 * 198);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 722);
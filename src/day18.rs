@@ -1,4 +1,9 @@
-use std::{cmp::Ordering, collections::BinaryHeap, str::FromStr};
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
 
 use crate::util::{load, Coord2D};
 
@@ -105,6 +110,53 @@ impl PartialOrd for HorEdge {
     }
 }
 
+// how the lagoon size is computed; both should agree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    // merge horizontal edges top to bottom
+    Sweep,
+    // shoelace area of the dig vertices plus the trench (Pick's theorem)
+    Shoelace,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PlanError {
+    // index of the last instruction and where it ends instead of the origin
+    NotClosed(usize, Coord2D<i64>),
+    // indices of two instructions whose trenches cross or touch in a single square
+    Crossing(usize, usize),
+    // indices of two instructions digging along the same trench
+    Overlap(usize, usize),
+}
+
+impl Display for PlanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanError::NotClosed(i, end) => write!(
+                f,
+                "Instruction {} ends the dig plan at ({}, {}), not at the origin",
+                i, end.x, end.y
+            ),
+            PlanError::Crossing(i, j) => {
+                write!(
+                    f,
+                    "Instruction {} crosses the trench of instruction {}",
+                    j, i
+                )
+            }
+            PlanError::Overlap(i, j) => {
+                write!(
+                    f,
+                    "Instruction {} digs over the trench of instruction {}",
+                    j, i
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
 struct State {
     digs: Vec<Dig>,
     edges: BinaryHeap<HorEdge>,
@@ -112,7 +164,11 @@ struct State {
 
 impl State {
     fn load(file: &str, convert: bool) -> Self {
-        let digs: Vec<Dig> = load::<String>(file)
+        State::parse(load::<String>(file), convert)
+    }
+
+    fn parse(lines: Vec<String>, convert: bool) -> Self {
+        let digs: Vec<Dig> = lines
             .into_iter()
             .map(|l| l.parse::<Dig>().unwrap())
             .map(|d| if convert { d.convert() } else { d })
@@ -139,6 +195,82 @@ impl State {
         }
     }
 
+    // corners of the trench, starting and ending at the origin
+    fn vertices(&self) -> Vec<Coord2D<i64>> {
+        let mut prev = Coord2D::new(0, 0);
+        let mut vertices = vec![prev];
+        for dig in self.digs.iter() {
+            let (dx, dy) = dig.dir.delta();
+            prev = Coord2D::new(prev.x + dx * dig.len, prev.y + dy * dig.len);
+            vertices.push(prev);
+        }
+        vertices
+    }
+
+    // the area engines need a simple closed loop
+    fn validate(&self) -> Result<(), PlanError> {
+        let vertices = self.vertices();
+        let end = *vertices.last().unwrap();
+        if end != Coord2D::new(0, 0) {
+            return Err(PlanError::NotClosed(self.digs.len() - 1, end));
+        }
+        let n = self.digs.len();
+        let bounds = |i: usize| {
+            let (a, b) = (vertices[i], vertices[i + 1]);
+            (a.x.min(b.x), a.x.max(b.x), a.y.min(b.y), a.y.max(b.y))
+        };
+        for j in 0..n {
+            let (jx1, jx2, jy1, jy2) = bounds(j);
+            for i in 0..j {
+                let (ix1, ix2, iy1, iy2) = bounds(i);
+                let (x1, x2) = (ix1.max(jx1), ix2.min(jx2));
+                let (y1, y2) = (iy1.max(jy1), iy2.min(jy2));
+                if x1 > x2 || y1 > y2 {
+                    continue;
+                }
+                // consecutive instructions share a corner, and so do the last and first
+                let adjacent = j == i + 1 || (i == 0 && j == n - 1);
+                if adjacent && x1 == x2 && y1 == y2 {
+                    continue;
+                }
+                return Err(if x1 == x2 && y1 == y2 {
+                    PlanError::Crossing(i, j)
+                } else {
+                    PlanError::Overlap(i, j)
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn shoelace(&self) -> u64 {
+        let vertices = self.vertices();
+        let twice_area = vertices
+            .windows(2)
+            .map(|w| w[0].x * w[1].y - w[1].x * w[0].y)
+            .sum::<i64>()
+            .unsigned_abs();
+        let boundary = self.digs.iter().map(|d| d.len as u64).sum::<u64>();
+        // interior points i = A - b/2 + 1, and the trench itself adds b
+        (twice_area + boundary) / 2 + 1
+    }
+
+    fn area(&mut self, engine: Engine) -> u64 {
+        match engine {
+            Engine::Sweep => {
+                self.edges.clear();
+                self.dig();
+                self.flood_fill()
+            }
+            Engine::Shoelace => self.shoelace(),
+        }
+    }
+
+    fn checked_area(&mut self, engine: Engine) -> Result<u64, PlanError> {
+        self.validate()?;
+        Ok(self.area(engine))
+    }
+
     fn combine(&self, mut edges: BinaryHeap<HorEdge>, edge: HorEdge) -> (u64, BinaryHeap<HorEdge>) {
         fn handle_edge(
             result: &mut BinaryHeap<HorEdge>,
@@ -276,22 +408,93 @@ impl State {
     }
 }
 
+pub fn lagoon_size(file: &str, convert: bool, engine: Engine) -> Result<u64, PlanError> {
+    State::load(file, convert).checked_area(engine)
+}
+
 pub fn part1() -> u64 {
-    let mut grid = State::load("data/day18.txt", false);
-    grid.dig();
-    grid.flood_fill()
+    lagoon_size("data/day18.txt", false, Engine::Sweep).unwrap()
 }
 
 pub fn part2() -> u64 {
-    let mut grid = State::load("data/day18.txt", true);
-    grid.dig();
-    grid.flood_fill()
+    lagoon_size("data/day18.txt", true, Engine::Sweep).unwrap()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn sample(convert: bool) -> State {
+        let lines = [
+            "R 6 (#70c710)",
+            "D 5 (#0dc571)",
+            "L 2 (#5713f0)",
+            "D 2 (#d2c081)",
+            "R 2 (#59c680)",
+            "D 2 (#411b91)",
+            "L 5 (#8ceee2)",
+            "U 2 (#caa173)",
+            "L 1 (#1b58a2)",
+            "U 2 (#caa171)",
+            "R 2 (#7807d2)",
+            "U 3 (#a77fa3)",
+            "L 2 (#015232)",
+            "U 2 (#7a21e3)",
+        ];
+        State::parse(lines.iter().map(|l| l.to_string()).collect(), convert)
+    }
+
+    #[test]
+    fn test_sample_engines() {
+        for engine in [Engine::Sweep, Engine::Shoelace] {
+            assert_eq!(sample(false).area(engine), 62);
+            assert_eq!(sample(true).area(engine), 952408144115);
+        }
+    }
+
+    fn plan(lines: &[&str]) -> State {
+        State::parse(
+            lines.iter().map(|l| format!("{} (#000000)", l)).collect(),
+            false,
+        )
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(sample(false).validate(), Ok(()));
+        assert_eq!(sample(true).validate(), Ok(()));
+        let mut open = sample(false);
+        open.digs.pop();
+        assert_eq!(
+            open.validate(),
+            Err(PlanError::NotClosed(12, Coord2D::new(0, 2)))
+        );
+        let crossing = plan(&["R 2", "D 2", "L 1", "U 3", "L 1", "D 1"]);
+        assert_eq!(crossing.validate(), Err(PlanError::Crossing(0, 3)));
+        // running into the end of an earlier trench along the same line
+        let touch = plan(&["R 2", "D 1", "R 1", "U 1", "L 1", "U 1", "L 2", "D 1"]);
+        assert_eq!(touch.validate(), Err(PlanError::Crossing(0, 4)));
+        let reversal = plan(&["R 3", "L 1", "D 1", "L 2", "U 1"]);
+        assert_eq!(reversal.validate(), Err(PlanError::Overlap(0, 1)));
+        let overlap = plan(&["R 2", "D 1", "R 1", "U 1", "L 2", "L 1"]);
+        assert_eq!(overlap.validate(), Err(PlanError::Overlap(0, 4)));
+        assert_eq!(
+            overlap.validate().unwrap_err().to_string(),
+            "Instruction 4 digs over the trench of instruction 0"
+        );
+        let mut state = plan(&["R 2", "D 2", "L 2", "U 2"]);
+        assert_eq!(state.checked_area(Engine::Shoelace), Ok(9));
+        assert!(plan(&["R 2", "D 2"]).checked_area(Engine::Sweep).is_err());
+    }
+
+    #[test]
+    fn test_engines_agree() {
+        for convert in [false, true] {
+            let mut state = State::load("data/day18.txt", convert);
+            assert_eq!(state.area(Engine::Sweep), state.area(Engine::Shoelace));
+        }
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(), 53844);
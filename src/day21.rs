@@ -11,7 +11,7 @@ type Coord = Coord2D<i32>;
 type Garden = Grid<i32, StepCount>;
 
 fn input(file: &str) -> Garden {
-    Grid::from_file(file, |c| match c {
+    Grid::from_file(file, |c, _| match c {
         '#' => None,
         '.' => Some(StepCount::UNKNOWN),
         'S' => Some(StepCount::EVEN),
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 use std::fmt::Debug;
 use std::fs::File;
 use std::hash::Hash;
@@ -90,3 +91,125 @@ impl<T, S> Grid<T, S> {
 pub fn char2num(ascii: char) -> u8 {
     ascii as u8 - '0' as u8
 }
+
+/**
+ * States visited by repeatedly stepping from an initial state, up to the
+ * first repeat: states[prefix..] repeat forever with the given period.
+ */
+#[derive(Debug, Clone)]
+pub struct Cycle<S> {
+    pub prefix: usize,
+    pub period: usize,
+    pub states: Vec<S>,
+}
+
+impl<S> Cycle<S> {
+    // index into states of the state after n steps
+    pub fn index_of(&self, n: usize) -> usize {
+        if n < self.prefix {
+            n
+        } else {
+            self.prefix + (n - self.prefix) % self.period
+        }
+    }
+
+    // fast-forward to the state after n steps
+    pub fn state_at(&self, n: usize) -> &S {
+        &self.states[self.index_of(n)]
+    }
+}
+
+pub fn find_cycle<S, F>(initial: S, mut step: F) -> Cycle<S>
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    let mut seen: HashMap<S, usize> = HashMap::new();
+    let mut states = vec![];
+    let mut state = initial;
+    loop {
+        if let Some(first) = seen.insert(state.clone(), states.len()) {
+            return Cycle {
+                prefix: first,
+                period: states.len() - first,
+                states,
+            };
+        }
+        let next = step(&state);
+        states.push(state);
+        state = next;
+    }
+}
+
+/**
+ * A* search from the start nodes to the first node satisfying is_goal.
+ * neighbours yields (node, cost of the move) pairs, heuristic must never
+ * overestimate the remaining cost (a zero heuristic gives Dijkstra).
+ * Returns the total cost and the nodes on the path, start and goal included.
+ */
+pub fn shortest_path<N, FN, I, FH, FG>(
+    starts: impl IntoIterator<Item = N>,
+    mut neighbours: FN,
+    heuristic: FH,
+    is_goal: FG,
+) -> Option<(usize, Vec<N>)>
+where
+    N: Clone + Eq + Hash,
+    FN: FnMut(&N) -> I,
+    I: IntoIterator<Item = (N, usize)>,
+    FH: Fn(&N) -> usize,
+    FG: Fn(&N) -> bool,
+{
+    // nodes are referred to by index, so they need no ordering
+    let mut nodes: Vec<N> = vec![];
+    let mut parents: Vec<Option<usize>> = vec![];
+    let mut best: Vec<usize> = vec![];
+    let mut index: HashMap<N, usize> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    for s in starts {
+        if !index.contains_key(&s) {
+            index.insert(s.clone(), nodes.len());
+            queue.push(Reverse((heuristic(&s), 0, nodes.len())));
+            nodes.push(s);
+            parents.push(None);
+            best.push(0);
+        }
+    }
+    while let Some(Reverse((_, cost, i))) = queue.pop() {
+        if cost > best[i] {
+            // already reached more cheaply
+            continue;
+        }
+        if is_goal(&nodes[i]) {
+            let mut path = vec![nodes[i].clone()];
+            let mut p = parents[i];
+            while let Some(j) = p {
+                path.push(nodes[j].clone());
+                p = parents[j];
+            }
+            path.reverse();
+            return Some((cost, path));
+        }
+        for (n, c) in neighbours(&nodes[i]) {
+            let new_cost = cost + c;
+            let j = match index.get(&n) {
+                Some(j) if best[*j] <= new_cost => continue,
+                Some(j) => {
+                    best[*j] = new_cost;
+                    parents[*j] = Some(i);
+                    *j
+                }
+                None => {
+                    let j = nodes.len();
+                    index.insert(n.clone(), j);
+                    nodes.push(n);
+                    parents.push(Some(i));
+                    best.push(new_cost);
+                    j
+                }
+            };
+            queue.push(Reverse((new_cost + heuristic(&nodes[j]), new_cost, j)));
+        }
+    }
+    None
+}